@@ -0,0 +1,163 @@
+use std::fmt;
+use std::str::FromStr;
+
+use omenix_lib::impl_serde_via_str;
+use omenix_lib::types::PerformanceMode;
+
+/// CPU frequency settings, unset fields are left alone
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CpuPolicy {
+    /// `scaling_governor` (e.g. `powersave`, `performance`)
+    pub governor: Option<String>,
+    /// `energy_performance_preference` (e.g. `power`, `balance_performance`)
+    pub epp: Option<String>,
+    /// Written to `intel_pstate/no_turbo` (inverted) or `cpufreq/boost`
+    pub turbo: Option<bool>,
+}
+
+impl fmt::Display for CpuPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{}/turbo {}",
+            self.governor.as_deref().unwrap_or("-"),
+            self.epp.as_deref().unwrap_or("-"),
+            match self.turbo {
+                Some(true) => "on",
+                Some(false) => "off",
+                None => "-",
+            }
+        )
+    }
+}
+
+/// CPU policy applied whenever `performance_mode` is selected
+#[derive(Debug, Clone, PartialEq)]
+pub struct CpuPolicyMapping {
+    pub performance_mode: PerformanceMode,
+    pub policy: CpuPolicy,
+}
+
+impl fmt::Display for CpuPolicyMapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}:{}",
+            self.performance_mode,
+            self.policy.governor.as_deref().unwrap_or(""),
+            self.policy.epp.as_deref().unwrap_or(""),
+            match self.policy.turbo {
+                Some(true) => "on",
+                Some(false) => "off",
+                None => "",
+            }
+        )
+    }
+}
+
+impl FromStr for CpuPolicyMapping {
+    type Err = String;
+
+    /// Parses `mode:governor:epp:turbo` with empty fields left unset,
+    /// e.g. `power-saver:powersave:power:off` or `balanced::balance_performance:`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').map(str::trim).collect();
+        let [mode, governor, epp, turbo] = parts.as_slice() else {
+            return Err(format!(
+                "Invalid CPU policy (expected mode:governor:epp:turbo): {}",
+                s
+            ));
+        };
+        let optional = |value: &str| (!value.is_empty()).then(|| value.to_string());
+        let turbo = match *turbo {
+            "" => None,
+            "on" | "true" => Some(true),
+            "off" | "false" => Some(false),
+            other => {
+                return Err(format!(
+                    "Invalid turbo setting (expected on/off): {}",
+                    other
+                ));
+            }
+        };
+        Ok(CpuPolicyMapping {
+            performance_mode: mode.parse()?,
+            policy: CpuPolicy {
+                governor: optional(governor),
+                epp: optional(epp),
+                turbo,
+            },
+        })
+    }
+}
+
+impl_serde_via_str!(CpuPolicyMapping);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_every_field() {
+        let mapping: CpuPolicyMapping = "power-saver:powersave:power:off".parse().unwrap();
+        assert_eq!(mapping.performance_mode, PerformanceMode::PowerSaver);
+        assert_eq!(
+            mapping.policy,
+            CpuPolicy {
+                governor: Some("powersave".to_string()),
+                epp: Some("power".to_string()),
+                turbo: Some(false),
+            }
+        );
+    }
+
+    #[test]
+    fn empty_fields_stay_unset() {
+        let mapping: CpuPolicyMapping = "balanced::balance_performance:".parse().unwrap();
+        assert_eq!(mapping.performance_mode, PerformanceMode::Balanced);
+        assert_eq!(
+            mapping.policy,
+            CpuPolicy {
+                governor: None,
+                epp: Some("balance_performance".to_string()),
+                turbo: None,
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_mappings() {
+        assert!(
+            "performance:performance"
+                .parse::<CpuPolicyMapping>()
+                .is_err()
+        );
+        assert!("turbo:performance::on".parse::<CpuPolicyMapping>().is_err());
+        assert!(
+            "performance:performance::maybe"
+                .parse::<CpuPolicyMapping>()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn mapping_round_trips_through_its_string_form() {
+        for text in [
+            "power-saver:powersave:power:off",
+            "balanced::balance_performance:",
+        ] {
+            let mapping: CpuPolicyMapping = text.parse().unwrap();
+            assert_eq!(mapping.to_string(), text);
+        }
+    }
+
+    #[test]
+    fn policy_display_marks_unset_fields() {
+        let policy = CpuPolicy {
+            governor: Some("performance".to_string()),
+            epp: None,
+            turbo: Some(true),
+        };
+        assert_eq!(policy.to_string(), "performance/-/turbo on");
+    }
+}
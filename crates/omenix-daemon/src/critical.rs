@@ -0,0 +1,75 @@
+use std::fmt;
+use std::time::Duration;
+
+/// Escalation steps taken one `temp_critical_duration` apart while the
+/// temperature stays critical with the fans already at max
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CriticalStage {
+    /// Drop the platform profile to its lowest power setting
+    PowerSaver,
+    /// Cap `scaling_max_freq` to `critical_cpu_max_freq`
+    CpuCap,
+    /// Run `critical_command`
+    Command,
+}
+
+impl CriticalStage {
+    pub const ALL: [CriticalStage; 3] = [
+        CriticalStage::PowerSaver,
+        CriticalStage::CpuCap,
+        CriticalStage::Command,
+    ];
+
+    /// Furthest stage reached after `elapsed` above the critical temperature
+    pub fn reached(elapsed: Duration, step: Duration) -> Option<Self> {
+        let steps = elapsed.as_secs() / step.as_secs().max(1);
+        match steps {
+            0 => None,
+            1 => Some(CriticalStage::PowerSaver),
+            2 => Some(CriticalStage::CpuCap),
+            _ => Some(CriticalStage::Command),
+        }
+    }
+}
+
+impl fmt::Display for CriticalStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CriticalStage::PowerSaver => write!(f, "power-saver"),
+            CriticalStage::CpuCap => write!(f, "cpu-cap"),
+            CriticalStage::Command => write!(f, "command"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stages_are_one_step_apart() {
+        let step = Duration::from_secs(30);
+        assert_eq!(CriticalStage::reached(Duration::from_secs(29), step), None);
+        assert_eq!(
+            CriticalStage::reached(Duration::from_secs(30), step),
+            Some(CriticalStage::PowerSaver)
+        );
+        assert_eq!(
+            CriticalStage::reached(Duration::from_secs(61), step),
+            Some(CriticalStage::CpuCap)
+        );
+        assert_eq!(
+            CriticalStage::reached(Duration::from_secs(600), step),
+            Some(CriticalStage::Command)
+        );
+    }
+
+    #[test]
+    fn zero_step_counts_whole_seconds() {
+        assert_eq!(CriticalStage::reached(Duration::ZERO, Duration::ZERO), None);
+        assert_eq!(
+            CriticalStage::reached(Duration::from_secs(2), Duration::ZERO),
+            Some(CriticalStage::CpuCap)
+        );
+    }
+}
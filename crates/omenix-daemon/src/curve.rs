@@ -0,0 +1,156 @@
+use omenix_lib::impl_serde_via_str;
+use std::fmt;
+use std::str::FromStr;
+
+/// A single point on the fan curve: at `temp` °C the fans run at `duty` percent
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CurvePoint {
+    pub temp: i32,
+    pub duty: u8,
+}
+
+impl fmt::Display for CurvePoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.temp, self.duty)
+    }
+}
+
+impl FromStr for CurvePoint {
+    type Err = String;
+
+    /// Parses `temp:duty`, e.g. `70:60`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (temp, duty) = s
+            .split_once(':')
+            .ok_or_else(|| format!("Invalid curve point (expected temp:duty): {}", s))?;
+        let temp = temp
+            .trim()
+            .parse::<i32>()
+            .map_err(|_| format!("Invalid curve temperature: {}", temp))?;
+        let duty = duty
+            .trim()
+            .trim_end_matches('%')
+            .parse::<u8>()
+            .ok()
+            .filter(|duty| *duty <= 100)
+            .ok_or_else(|| format!("Invalid curve duty (expected 0-100): {}", duty))?;
+        Ok(CurvePoint { temp, duty })
+    }
+}
+
+impl_serde_via_str!(CurvePoint);
+
+/// Temperature-to-duty curve with points sorted by temperature
+#[derive(Debug, Clone)]
+pub struct FanCurve {
+    points: Vec<CurvePoint>,
+}
+
+impl FanCurve {
+    /// Returns None if no points are configured
+    pub fn new(points: &[CurvePoint]) -> Option<Self> {
+        if points.is_empty() {
+            return None;
+        }
+        let mut points = points.to_vec();
+        points.sort_by_key(|point| point.temp);
+        Some(Self { points })
+    }
+
+    /// Linearly interpolated duty in percent for a temperature in Celsius,
+    /// clamped to the first and last points outside the curve
+    pub fn duty_at(&self, temp_celsius: i32) -> u8 {
+        let first = self.points[0];
+        let last = self.points[self.points.len() - 1];
+
+        if temp_celsius <= first.temp {
+            return first.duty;
+        }
+        if temp_celsius >= last.temp {
+            return last.duty;
+        }
+
+        for pair in self.points.windows(2) {
+            let (low, high) = (pair[0], pair[1]);
+            if temp_celsius <= high.temp {
+                let span = high.temp - low.temp;
+                if span == 0 {
+                    return high.duty;
+                }
+                let offset = temp_celsius - low.temp;
+                let delta = i32::from(high.duty) - i32::from(low.duty);
+                return (i32::from(low.duty) + delta * offset / span) as u8;
+            }
+        }
+
+        last.duty
+    }
+
+    /// Temperature halfway between the first and last points, used as the
+    /// Max/BIOS switch point on hardware without PWM duty control
+    pub fn midpoint(&self) -> i32 {
+        let first = self.points[0];
+        let last = self.points[self.points.len() - 1];
+        (first.temp + last.temp) / 2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn curve(points: &[&str]) -> FanCurve {
+        let points: Vec<CurvePoint> = points.iter().map(|point| point.parse().unwrap()).collect();
+        FanCurve::new(&points).unwrap()
+    }
+
+    #[test]
+    fn parses_temp_duty_points() {
+        assert_eq!(
+            "70:60".parse::<CurvePoint>(),
+            Ok(CurvePoint { temp: 70, duty: 60 })
+        );
+        assert_eq!(
+            " 80 : 90% ".parse::<CurvePoint>(),
+            Ok(CurvePoint { temp: 80, duty: 90 })
+        );
+        assert!("70".parse::<CurvePoint>().is_err());
+        assert!("70:101".parse::<CurvePoint>().is_err());
+        assert!("hot:50".parse::<CurvePoint>().is_err());
+    }
+
+    #[test]
+    fn interpolates_between_points() {
+        let curve = curve(&["50:20", "70:60", "90:100"]);
+        assert_eq!(curve.duty_at(50), 20);
+        assert_eq!(curve.duty_at(60), 40);
+        assert_eq!(curve.duty_at(70), 60);
+        assert_eq!(curve.duty_at(85), 90);
+    }
+
+    #[test]
+    fn clamps_outside_the_curve() {
+        let curve = curve(&["50:20", "90:100"]);
+        assert_eq!(curve.duty_at(20), 20);
+        assert_eq!(curve.duty_at(110), 100);
+    }
+
+    #[test]
+    fn sorts_points_by_temperature() {
+        let curve = curve(&["90:100", "50:20", "70:60"]);
+        assert_eq!(curve.duty_at(60), 40);
+        assert_eq!(curve.midpoint(), 70);
+    }
+
+    #[test]
+    fn duplicate_temperatures_do_not_divide_by_zero() {
+        let curve = curve(&["50:20", "70:40", "70:80", "90:100"]);
+        assert_eq!(curve.duty_at(70), 40);
+        assert_eq!(curve.duty_at(80), 90);
+    }
+
+    #[test]
+    fn empty_curve_is_rejected() {
+        assert!(FanCurve::new(&[]).is_none());
+    }
+}
@@ -0,0 +1,277 @@
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use serde::Serialize;
+use std::path::Path;
+
+use crate::AppConfig;
+use crate::hardware::{self, HardwareBackend, SysfsBackend};
+use crate::quirks;
+
+/// `omenix-daemon doctor` subcommand definition
+pub fn command() -> Command {
+    Command::new("doctor")
+        .about("Check whether this machine supports Omenix and print a report")
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .action(ArgAction::SetTrue)
+                .help("Print the report as JSON"),
+        )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug, Serialize)]
+struct Check {
+    name: String,
+    status: CheckStatus,
+    detail: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hint: Option<String>,
+}
+
+impl Check {
+    fn new(name: impl Into<String>, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status,
+            detail: detail.into(),
+            hint: None,
+        }
+    }
+
+    fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Sensor {
+    id: String,
+    temp_celsius: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct Report {
+    board_name: Option<String>,
+    fan_channels: Vec<String>,
+    sensors: Vec<Sensor>,
+    profile_choices: Vec<String>,
+    checks: Vec<Check>,
+    passed: bool,
+}
+
+/// Runs every check and prints the report, returns whether all checks passed
+pub fn run(config: &AppConfig, matches: &ArgMatches) -> bool {
+    let backend = SysfsBackend::new(&config.sysfs_root, config.fan_channels.clone());
+    let report = collect(config, &backend);
+
+    if matches.get_flag("json") {
+        match serde_json::to_string_pretty(&report) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("Failed to serialize report: {}", e),
+        }
+    } else {
+        print_report(&report);
+    }
+
+    report.passed
+}
+
+fn collect(config: &AppConfig, backend: &SysfsBackend) -> Report {
+    let mut checks = Vec::new();
+
+    if config.sysfs_root == Path::new("/") && unsafe { libc::geteuid() } != 0 {
+        checks.push(
+            Check::new(
+                "root",
+                CheckStatus::Warn,
+                "not running as root, writability is judged from file permissions only",
+            )
+            .with_hint("Run `sudo omenix-daemon doctor` to test access as the daemon would"),
+        );
+    }
+
+    if backend.platform_device_present() {
+        checks.push(Check::new(
+            "hp-wmi",
+            CheckStatus::Pass,
+            "platform device found",
+        ));
+    } else {
+        checks.push(
+            Check::new("hp-wmi", CheckStatus::Fail, "platform device not found").with_hint(
+                "Load the driver with `sudo modprobe hp-wmi` and check `lsmod | grep hp_wmi`",
+            ),
+        );
+    }
+
+    let board_name = hardware::read_board_name(&config.sysfs_root).ok();
+    match &board_name {
+        Some(board) => checks.push(Check::new("board", CheckStatus::Pass, board.as_str())),
+        None => checks.push(
+            Check::new("board", CheckStatus::Warn, "DMI board name not readable")
+                .with_hint("Look for `Product Name` in `sudo dmidecode` output instead"),
+        ),
+    }
+    match board_name.as_deref().and_then(quirks::lookup) {
+        Some(quirk) => checks.push(Check::new("quirk", CheckStatus::Pass, quirk.description)),
+        None => checks.push(Check::new(
+            "quirk",
+            CheckStatus::Pass,
+            "no quirk entry for this board, using plain defaults",
+        )),
+    }
+
+    for attribute in backend.attributes() {
+        let failure = if attribute.optional {
+            CheckStatus::Warn
+        } else {
+            CheckStatus::Fail
+        };
+        let path = attribute.path.display();
+        let check = if !attribute.exists {
+            Check::new(&attribute.purpose, failure, format!("{} not found", path))
+        } else if attribute.needs_write && !attribute.writable {
+            Check::new(
+                &attribute.purpose,
+                failure,
+                format!("{} not writable", path),
+            )
+        } else {
+            Check::new(&attribute.purpose, CheckStatus::Pass, path.to_string())
+        };
+        let check = if check.status == CheckStatus::Pass {
+            check
+        } else {
+            check.with_hint(attribute_hint(&attribute.purpose, board_name.as_deref()))
+        };
+        checks.push(check);
+    }
+
+    let fan_channels = backend.fan_channels().unwrap_or_default();
+
+    let sensors: Vec<Sensor> = backend
+        .read_temperatures()
+        .unwrap_or_default()
+        .iter()
+        .map(|reading| Sensor {
+            id: reading.id(),
+            temp_celsius: f64::from(reading.temp) / 1000.0,
+        })
+        .collect();
+    match backend.read_temperature(&config.temp_sensors, config.temp_aggregation) {
+        Ok(reading) => checks.push(Check::new(
+            "temperature",
+            CheckStatus::Pass,
+            format!("{}°C from {}", reading.temp / 1000, reading.source),
+        )),
+        Err(e) => checks.push(
+            Check::new("temperature", CheckStatus::Fail, e.to_string()).with_hint(
+                "Pick sensors listed below with `temp_sensors` in /etc/omenix-daemon.yaml",
+            ),
+        ),
+    }
+
+    let profile_choices: Vec<String> = backend
+        .read_performance_choices()
+        .unwrap_or_default()
+        .iter()
+        .map(|mode| mode.to_string())
+        .collect();
+
+    let passed = checks.iter().all(|check| check.status != CheckStatus::Fail);
+    Report {
+        board_name,
+        fan_channels,
+        sensors,
+        profile_choices,
+        checks,
+        passed,
+    }
+}
+
+/// Actionable hint for a missing or read-only sysfs attribute
+fn attribute_hint(purpose: &str, board_name: Option<&str>) -> String {
+    if purpose.starts_with("fan mode") {
+        format!(
+            "Board {} may be missing from the hp-wmi driver's board list, see the README",
+            board_name.unwrap_or("(unknown)")
+        )
+    } else if purpose.starts_with("fan duty") {
+        "Manual mode is unavailable and Curve mode falls back to toggling Max/BIOS".to_string()
+    } else if purpose.starts_with("fan speed") {
+        "Fan RPM will not be shown in the tray or status".to_string()
+    } else if purpose == "platform profile choices" {
+        "The tray will offer the default profiles only".to_string()
+    } else if purpose.starts_with("keyboard") {
+        "Keyboard backlight control and the idle timeout are unavailable".to_string()
+    } else if purpose.starts_with("charge start") {
+        "Only the end of the charge limit can be set on this battery".to_string()
+    } else if purpose.starts_with("charge") {
+        "The battery charge limit cannot be set on this machine".to_string()
+    } else if purpose.starts_with("CPU") {
+        "cpu_policies and critical_cpu_max_freq cannot use this setting".to_string()
+    } else {
+        "Performance profiles need ACPI platform_profile support from the hp-wmi driver".to_string()
+    }
+}
+
+fn print_report(report: &Report) {
+    println!(
+        "Omenix doctor (board: {})",
+        report.board_name.as_deref().unwrap_or("unknown")
+    );
+    println!();
+    for check in &report.checks {
+        let status = match check.status {
+            CheckStatus::Pass => "PASS",
+            CheckStatus::Warn => "WARN",
+            CheckStatus::Fail => "FAIL",
+        };
+        println!("[{}] {}: {}", status, check.name, check.detail);
+        if let Some(hint) = &check.hint {
+            println!("       hint: {}", hint);
+        }
+    }
+
+    println!();
+    println!("Sensors:");
+    if report.sensors.is_empty() {
+        println!("  none found");
+    }
+    for sensor in &report.sensors {
+        println!("  {}: {:.1}°C", sensor.id, sensor.temp_celsius);
+    }
+    println!(
+        "Profile choices: {}",
+        if report.profile_choices.is_empty() {
+            "unknown".to_string()
+        } else {
+            report.profile_choices.join(", ")
+        }
+    );
+
+    let failed = report
+        .checks
+        .iter()
+        .filter(|check| check.status == CheckStatus::Fail)
+        .count();
+    let warnings = report
+        .checks
+        .iter()
+        .filter(|check| check.status == CheckStatus::Warn)
+        .count();
+    println!();
+    if report.passed {
+        println!("Result: supported ({} warnings)", warnings);
+    } else {
+        println!("Result: {} checks failed, {} warnings", failed, warnings);
+    }
+}
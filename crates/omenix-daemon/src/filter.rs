@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Smoothing applied to temperature readings before the monitor acts on them
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum TempFilter {
+    None,
+    Ema,
+    Median,
+    /// Rolling median to drop spikes, then EMA
+    Both,
+}
+
+/// Filter state carried between readings
+#[derive(Debug, Clone)]
+pub struct TempSmoother {
+    filter: TempFilter,
+    alpha: f64,
+    window: usize,
+    samples: VecDeque<i32>,
+    ema: Option<f64>,
+}
+
+impl TempSmoother {
+    /// `alpha` is the EMA weight of the newest reading (0-1), `window` the
+    /// number of readings the median is taken over
+    pub fn new(filter: TempFilter, alpha: f64, window: usize) -> Self {
+        Self {
+            filter,
+            alpha: alpha.clamp(0.0, 1.0),
+            window: window.max(1),
+            samples: VecDeque::new(),
+            ema: None,
+        }
+    }
+
+    /// Filtered temperature in millicelsius for a raw reading
+    pub fn apply(&mut self, temp: i32) -> i32 {
+        match self.filter {
+            TempFilter::None => temp,
+            TempFilter::Ema => self.ema(temp),
+            TempFilter::Median => self.median(temp),
+            TempFilter::Both => {
+                let median = self.median(temp);
+                self.ema(median)
+            }
+        }
+    }
+
+    fn median(&mut self, temp: i32) -> i32 {
+        self.samples.push_back(temp);
+        if self.samples.len() > self.window {
+            self.samples.pop_front();
+        }
+        let mut sorted: Vec<i32> = self.samples.iter().copied().collect();
+        sorted.sort_unstable();
+        sorted[sorted.len() / 2]
+    }
+
+    fn ema(&mut self, temp: i32) -> i32 {
+        let ema = match self.ema {
+            Some(ema) => ema + self.alpha * (f64::from(temp) - ema),
+            None => f64::from(temp),
+        };
+        self.ema = Some(ema);
+        ema.round() as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(filter: TempFilter, readings: &[i32]) -> Vec<i32> {
+        let mut smoother = TempSmoother::new(filter, 0.5, 3);
+        readings.iter().map(|temp| smoother.apply(*temp)).collect()
+    }
+
+    #[test]
+    fn none_passes_readings_through() {
+        assert_eq!(
+            run(TempFilter::None, &[50_000, 90_000, 50_000]),
+            [50_000, 90_000, 50_000]
+        );
+    }
+
+    #[test]
+    fn ema_starts_at_the_first_reading() {
+        assert_eq!(
+            run(TempFilter::Ema, &[50_000, 60_000, 60_000]),
+            [50_000, 55_000, 57_500]
+        );
+    }
+
+    #[test]
+    fn median_drops_single_spikes() {
+        assert_eq!(
+            run(TempFilter::Median, &[50_000, 50_000, 95_000, 51_000]),
+            [50_000, 50_000, 50_000, 51_000]
+        );
+    }
+
+    #[test]
+    fn both_smooths_the_median() {
+        assert_eq!(
+            run(TempFilter::Both, &[50_000, 50_000, 95_000, 60_000, 60_000]),
+            [50_000, 50_000, 50_000, 55_000, 57_500]
+        );
+    }
+
+    #[test]
+    fn parameters_are_clamped() {
+        let mut smoother = TempSmoother::new(TempFilter::Ema, 7.0, 0);
+        assert_eq!(smoother.apply(50_000), 50_000);
+        assert_eq!(smoother.apply(60_000), 60_000);
+
+        let mut smoother = TempSmoother::new(TempFilter::Median, 0.3, 0);
+        assert_eq!(smoother.apply(50_000), 50_000);
+        assert_eq!(smoother.apply(90_000), 90_000);
+    }
+}
@@ -0,0 +1,1162 @@
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use tracing::{debug, error, info, instrument, warn};
+
+use crate::cpu::CpuPolicy;
+use crate::power::PowerSource;
+use crate::sensors::{
+    self, SensorKind, SensorReading, SensorSelector, TempAggregation, TemperatureReading,
+};
+use omenix_lib::types::{ChargeLimit, HardwareFanMode, KeyboardBacklight, PerformanceMode};
+
+// Paths are relative to the sysfs root so the daemon can run against a fake tree
+const TEMP_SENSOR_PATH: &str = "sys/class/thermal/thermal_zone*/temp";
+const HWMON_TEMP_SENSOR_PATH: &str = "sys/class/hwmon/hwmon*/temp*_input";
+const FAN_CONTROL_PATH: &str = "sys/devices/platform/hp-wmi/hwmon/hwmon*/pwm*_enable";
+const FAN_SPEED_PATH: &str = "sys/devices/platform/hp-wmi/hwmon/hwmon*/fan*_input";
+const PERFORMANCE_PROFILE_PATH: &str = "sys/firmware/acpi/platform_profile";
+const PERFORMANCE_CHOICES_PATH: &str = "sys/firmware/acpi/platform_profile_choices";
+const PLATFORM_DEVICE_PATH: &str = "sys/devices/platform/hp-wmi";
+const BOARD_NAME_PATH: &str = "sys/class/dmi/id/board_name";
+const KBD_BACKLIGHT_PATH: &str = "sys/class/leds/*kbd_backlight*";
+const INTERRUPTS_PATH: &str = "proc/interrupts";
+const I8042_KEYBOARD_IRQ: &str = "1:";
+const BATTERY_PATH: &str = "sys/class/power_supply/BAT*";
+const POWER_SUPPLY_ONLINE_PATH: &str = "sys/class/power_supply/*/online";
+const CPU_GOVERNOR_PATH: &str = "sys/devices/system/cpu/cpu[0-9]*/cpufreq/scaling_governor";
+const CPU_EPP_PATH: &str = "sys/devices/system/cpu/cpu[0-9]*/cpufreq/energy_performance_preference";
+const CPU_MAX_FREQ_PATH: &str = "sys/devices/system/cpu/cpu[0-9]*/cpufreq/scaling_max_freq";
+const INTEL_NO_TURBO_PATH: &str = "sys/devices/system/cpu/intel_pstate/no_turbo";
+const CPUFREQ_BOOST_PATH: &str = "sys/devices/system/cpu/cpufreq/boost";
+
+/// Per-channel outcome of a fan mode write
+#[derive(Debug, Clone, Default)]
+pub struct FanWriteReport {
+    pub channels: Vec<(String, Result<(), String>)>,
+}
+
+impl FanWriteReport {
+    pub fn all_failed(&self) -> bool {
+        self.channels.iter().all(|(_, result)| result.is_err())
+    }
+}
+
+impl fmt::Display for FanWriteReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = self
+            .channels
+            .iter()
+            .map(|(channel, result)| match result {
+                Ok(()) => format!("{}: ok", channel),
+                Err(e) => format!("{}: failed ({})", channel, e),
+            })
+            .collect();
+        write!(f, "{}", parts.join("; "))
+    }
+}
+
+/// Charge level and thresholds of the first battery
+#[derive(Debug, Clone)]
+pub struct BatteryStatus {
+    /// power_supply name (e.g. `BAT0`)
+    pub name: String,
+    /// Charge level in percent
+    pub capacity: Option<u8>,
+    /// None if the battery has no `charge_control_end_threshold`
+    pub charge_limit: Option<ChargeLimit>,
+}
+
+/// A sysfs attribute the daemon reads or writes, as found on this machine
+#[derive(Debug, Clone)]
+pub struct SysfsAttribute {
+    /// What the attribute is used for (e.g. `fan mode hwmon3/pwm1`)
+    pub purpose: String,
+    pub path: PathBuf,
+    pub exists: bool,
+    pub writable: bool,
+    /// Whether the daemon needs to write the attribute
+    pub needs_write: bool,
+    /// Whether the daemon can work without the attribute
+    pub optional: bool,
+}
+
+/// Hardware access used by the daemon (fan control, platform profile, sensors)
+pub trait HardwareBackend: fmt::Debug + Send + Sync {
+    /// Lists the fan control channels the daemon will write to (e.g. `hwmon3/pwm1`)
+    fn fan_channels(&self) -> Result<Vec<String>, io::Error>;
+    /// Whether every selected fan channel exposes a writable `pwmN` duty attribute
+    fn supports_manual_duty(&self) -> bool;
+    /// Writes the mode to every fan control channel. Fails only if no channel could be written.
+    fn write_fan_mode(&self, mode: HardwareFanMode) -> Result<FanWriteReport, io::Error>;
+    /// Reads back the mode currently set on the first fan control channel
+    fn read_fan_mode(&self) -> Result<HardwareFanMode, io::Error>;
+    fn write_performance_mode(&self, mode: PerformanceMode) -> Result<(), io::Error>;
+    fn read_performance_mode(&self) -> Result<PerformanceMode, io::Error>;
+    /// Returns the platform profiles the firmware accepts
+    fn read_performance_choices(&self) -> Result<Vec<PerformanceMode>, io::Error>;
+    /// Returns every readable thermal zone and hwmon temperature sensor
+    fn read_temperatures(&self) -> Result<Vec<SensorReading>, io::Error>;
+    /// Reads the selected sensors and aggregates them into one temperature
+    fn read_temperature(
+        &self,
+        selectors: &[SensorSelector],
+        aggregation: TempAggregation,
+    ) -> Result<TemperatureReading, io::Error> {
+        let readings = self.read_temperatures()?;
+        let reading = sensors::aggregate(&readings, selectors, aggregation)?;
+        debug!(
+            "Temperature read: {}°C from {}",
+            reading.temp / 1000,
+            reading.source
+        );
+        Ok(reading)
+    }
+    /// Returns every fan tachometer reading in RPM, ordered by fan index
+    fn read_fan_speeds(&self) -> Result<Vec<u32>, io::Error>;
+    fn read_keyboard_backlight(&self) -> Result<KeyboardBacklight, io::Error>;
+    fn write_keyboard_backlight(&self, brightness: u32) -> Result<(), io::Error>;
+    /// Returns a counter that changes whenever the built-in keyboard is used
+    fn read_keyboard_activity(&self) -> Result<u64, io::Error>;
+    fn read_battery(&self) -> Result<BatteryStatus, io::Error>;
+    /// Writes the charge thresholds to every battery that supports them
+    fn write_charge_limit(&self, limit: ChargeLimit) -> Result<(), io::Error>;
+    /// AC if any external power supply is online, Battery otherwise
+    fn read_power_source(&self) -> Result<PowerSource, io::Error>;
+    /// Reads the CPU policy of the first CPU (fields the system lacks are None)
+    fn read_cpu_policy(&self) -> Result<CpuPolicy, io::Error>;
+    /// Applies the set fields of `policy` to every CPU
+    fn write_cpu_policy(&self, policy: &CpuPolicy) -> Result<(), io::Error>;
+    /// `scaling_max_freq` of every CPU in kHz, in CPU order
+    fn read_cpu_max_freqs(&self) -> Result<Vec<u32>, io::Error>;
+    /// Writes `scaling_max_freq` in kHz to each CPU in the order `read_cpu_max_freqs` returns them
+    fn write_cpu_max_freqs(&self, freqs: &[u32]) -> Result<(), io::Error>;
+}
+
+/// hp-wmi backend reading and writing sysfs attributes below `root`
+#[derive(Debug, Clone)]
+pub struct SysfsBackend {
+    root: PathBuf,
+    /// Channels to control, matched against `hwmonN/pwmM` or `pwmM` (empty means all)
+    fan_channel_filter: Vec<String>,
+    /// Cleared for boards whose firmware ignores `pwmN` duty writes
+    manual_duty: bool,
+}
+
+impl SysfsBackend {
+    pub fn new(root: impl Into<PathBuf>, fan_channel_filter: Vec<String>) -> Self {
+        let root = root.into();
+        info!(
+            "Using sysfs root: {:?}, fan channel filter: {:?}",
+            root, fan_channel_filter
+        );
+        Self {
+            root,
+            fan_channel_filter,
+            manual_duty: true,
+        }
+    }
+
+    /// Disable manual duty control regardless of the `pwmN` attributes
+    pub fn with_manual_duty(mut self, manual_duty: bool) -> Self {
+        self.manual_duty = manual_duty;
+        self
+    }
+
+    /// Resolve a path relative to the sysfs root
+    fn path(&self, relative: &str) -> PathBuf {
+        self.root.join(relative)
+    }
+
+    /// Expand a glob pattern relative to the sysfs root
+    fn glob(&self, pattern: &str) -> Result<Vec<PathBuf>, io::Error> {
+        let root = glob::Pattern::escape(&self.root.to_string_lossy());
+        let full_pattern = format!("{}/{}", root.trim_end_matches('/'), pattern);
+        let mut paths: Vec<_> = glob::glob(&full_pattern)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?
+            .filter_map(Result::ok)
+            .collect();
+        paths.sort();
+        Ok(paths)
+    }
+
+    /// Whether the hp-wmi platform device is present
+    pub fn platform_device_present(&self) -> bool {
+        self.path(PLATFORM_DEVICE_PATH).is_dir()
+    }
+
+    /// Lists every sysfs attribute the daemon uses along with its access
+    pub fn attributes(&self) -> Vec<SysfsAttribute> {
+        let attribute =
+            |purpose: String, path: PathBuf, needs_write: bool, optional: bool| SysfsAttribute {
+                purpose,
+                exists: path.exists(),
+                writable: is_writable(&path),
+                path,
+                needs_write,
+                optional,
+            };
+
+        let mut attributes = Vec::new();
+        match self.fan_control_paths() {
+            Ok(paths) => {
+                for (channel, enable_path) in paths {
+                    let duty = duty_path(&enable_path);
+                    attributes.push(attribute(
+                        format!("fan mode {}", channel),
+                        enable_path,
+                        true,
+                        false,
+                    ));
+                    attributes.push(attribute(format!("fan duty {}", channel), duty, true, true));
+                }
+            }
+            Err(_) => attributes.push(attribute(
+                "fan mode".to_string(),
+                self.path(FAN_CONTROL_PATH),
+                true,
+                false,
+            )),
+        }
+        match self.glob(FAN_SPEED_PATH) {
+            Ok(paths) if !paths.is_empty() => {
+                for path in paths {
+                    let name = path
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_default();
+                    attributes.push(attribute(format!("fan speed {}", name), path, false, true));
+                }
+            }
+            _ => attributes.push(attribute(
+                "fan speed".to_string(),
+                self.path(FAN_SPEED_PATH),
+                false,
+                true,
+            )),
+        }
+        attributes.push(attribute(
+            "platform profile".to_string(),
+            self.path(PERFORMANCE_PROFILE_PATH),
+            true,
+            false,
+        ));
+        attributes.push(attribute(
+            "platform profile choices".to_string(),
+            self.path(PERFORMANCE_CHOICES_PATH),
+            false,
+            true,
+        ));
+
+        // Optional features, globbed paths are listed by their first match
+        let first_match = |pattern: &str| {
+            self.glob(pattern)
+                .ok()
+                .and_then(|paths| paths.into_iter().next())
+                .unwrap_or_else(|| self.path(pattern))
+        };
+        let kbd_backlight = first_match(KBD_BACKLIGHT_PATH);
+        attributes.push(attribute(
+            "keyboard backlight".to_string(),
+            kbd_backlight.join("brightness"),
+            true,
+            true,
+        ));
+        attributes.push(attribute(
+            "keyboard backlight max".to_string(),
+            kbd_backlight.join("max_brightness"),
+            false,
+            true,
+        ));
+        attributes.push(attribute(
+            "keyboard activity".to_string(),
+            self.path(INTERRUPTS_PATH),
+            false,
+            true,
+        ));
+        match self.glob(BATTERY_PATH) {
+            Ok(batteries) if !batteries.is_empty() => {
+                for battery in batteries {
+                    let name = battery
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_default();
+                    for (bound, file) in [
+                        ("end", "charge_control_end_threshold"),
+                        ("start", "charge_control_start_threshold"),
+                    ] {
+                        attributes.push(attribute(
+                            format!("charge {} threshold {}", bound, name),
+                            battery.join(file),
+                            true,
+                            true,
+                        ));
+                    }
+                }
+            }
+            _ => attributes.push(attribute(
+                "charge end threshold".to_string(),
+                self.path(BATTERY_PATH).join("charge_control_end_threshold"),
+                true,
+                true,
+            )),
+        }
+        for (purpose, pattern) in [
+            ("CPU governor", CPU_GOVERNOR_PATH),
+            ("CPU energy performance preference", CPU_EPP_PATH),
+            ("CPU max frequency", CPU_MAX_FREQ_PATH),
+        ] {
+            attributes.push(attribute(
+                purpose.to_string(),
+                first_match(pattern),
+                true,
+                true,
+            ));
+        }
+        // Intel exposes no_turbo, other drivers the cpufreq boost switch
+        let no_turbo = self.path(INTEL_NO_TURBO_PATH);
+        let turbo = if no_turbo.exists() {
+            no_turbo
+        } else {
+            self.path(CPUFREQ_BOOST_PATH)
+        };
+        attributes.push(attribute("CPU turbo".to_string(), turbo, true, true));
+        attributes
+    }
+
+    /// Find the LED class directory of the keyboard backlight
+    fn keyboard_backlight_path(&self) -> Result<PathBuf, io::Error> {
+        self.glob(KBD_BACKLIGHT_PATH)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No keyboard backlight found"))
+    }
+
+    /// Find the `pwm*_enable` files of the selected fan channels, keyed by channel name
+    fn fan_control_paths(&self) -> Result<Vec<(String, PathBuf)>, io::Error> {
+        let channels: Vec<_> = self
+            .glob(FAN_CONTROL_PATH)?
+            .into_iter()
+            .filter_map(|path| {
+                let pwm = path.file_name()?.to_str()?.strip_suffix("_enable")?;
+                let hwmon = path.parent()?.file_name()?.to_str()?;
+                Some((format!("{}/{}", hwmon, pwm), path.clone()))
+            })
+            .filter(|(channel, _)| {
+                self.fan_channel_filter.is_empty()
+                    || self.fan_channel_filter.iter().any(|wanted| {
+                        wanted == channel || channel.split('/').nth(1) == Some(wanted.as_str())
+                    })
+            })
+            .collect();
+
+        if channels.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "No fan control file found",
+            ));
+        }
+
+        Ok(channels)
+    }
+}
+
+/// Reads the DMI board name (e.g. `8BAB`) below the sysfs root
+pub fn read_board_name(root: &Path) -> Result<String, io::Error> {
+    read_sysfs_value(&root.join(BOARD_NAME_PATH))
+}
+
+/// Path of the `pwmN` duty attribute next to a `pwmN_enable` file
+fn duty_path(enable_path: &Path) -> PathBuf {
+    let name = enable_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.strip_suffix("_enable"))
+        .unwrap_or("pwm1");
+    enable_path.with_file_name(name)
+}
+
+/// Read a sysfs attribute, trimming the trailing newline
+fn read_sysfs_value(path: &Path) -> Result<String, io::Error> {
+    Ok(fs::read_to_string(path)?.trim().to_string())
+}
+
+fn is_writable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path)
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o222 != 0)
+        .unwrap_or(false)
+}
+
+fn write_sysfs_value(path: &Path, value: &str) -> Result<(), io::Error> {
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .open(path)?;
+
+    file.write_all(value.as_bytes())?;
+    file.flush()
+}
+
+impl HardwareBackend for SysfsBackend {
+    fn fan_channels(&self) -> Result<Vec<String>, io::Error> {
+        Ok(self
+            .fan_control_paths()?
+            .into_iter()
+            .map(|(channel, _)| channel)
+            .collect())
+    }
+
+    fn supports_manual_duty(&self) -> bool {
+        if !self.manual_duty {
+            return false;
+        }
+        match self.fan_control_paths() {
+            Ok(paths) => paths
+                .iter()
+                .all(|(_, enable_path)| is_writable(&duty_path(enable_path))),
+            Err(_) => false,
+        }
+    }
+
+    #[instrument(level = "debug")]
+    fn write_fan_mode(&self, mode: HardwareFanMode) -> Result<FanWriteReport, io::Error> {
+        let (value, duty) = match mode {
+            HardwareFanMode::Max => ("0", None),
+            HardwareFanMode::Bios => ("2", None),
+            // pwm duty is 0-255
+            HardwareFanMode::Manual(percent) => {
+                ("1", Some((u32::from(percent.min(100)) * 255 + 50) / 100))
+            }
+        };
+
+        info!(
+            "Writing fan mode: {:?} (value: {}, duty: {:?})",
+            mode, value, duty
+        );
+
+        let mut report = FanWriteReport::default();
+        for (channel, fan_path) in self.fan_control_paths()? {
+            debug!("Writing to fan control file: {:?}", fan_path);
+            let result = write_sysfs_value(&fan_path, value)
+                .and_then(|()| match duty {
+                    Some(duty) => write_sysfs_value(&duty_path(&fan_path), &duty.to_string())
+                        .inspect_err(|_| {
+                            // Never leave a channel in manual mode at an unknown duty
+                            if let Err(e) = write_sysfs_value(&fan_path, "2") {
+                                error!("Failed to return {} to BIOS control: {}", channel, e);
+                            }
+                        }),
+                    None => Ok(()),
+                })
+                .map_err(|e| {
+                    warn!("Failed to write fan mode to {}: {}", channel, e);
+                    e.to_string()
+                });
+            report.channels.push((channel, result));
+        }
+
+        if report.all_failed() {
+            return Err(io::Error::other(format!(
+                "Failed to write any fan channel: {}",
+                report
+            )));
+        }
+
+        info!("Successfully wrote fan mode: {:?} ({})", mode, report);
+        Ok(report)
+    }
+
+    fn read_fan_mode(&self) -> Result<HardwareFanMode, io::Error> {
+        let mut modes = Vec::new();
+        for (channel, enable_path) in self.fan_control_paths()? {
+            let mode = match read_sysfs_value(&enable_path)?.as_str() {
+                "0" => HardwareFanMode::Max,
+                "2" => HardwareFanMode::Bios,
+                "1" => {
+                    let duty = read_sysfs_value(&duty_path(&enable_path))?
+                        .parse::<u32>()
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                    HardwareFanMode::Manual(((duty.min(255) * 100 + 127) / 255) as u8)
+                }
+                other => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Unknown fan mode value {} in {}", other, channel),
+                    ));
+                }
+            };
+            modes.push((channel, mode));
+        }
+
+        let (first_channel, first_mode) = modes[0].clone();
+        for (channel, mode) in &modes[1..] {
+            if *mode != first_mode {
+                warn!(
+                    "Fan channels disagree: {} is {:?} but {} is {:?}",
+                    first_channel, first_mode, channel, mode
+                );
+            }
+        }
+
+        debug!("Read fan mode: {:?}", first_mode);
+        Ok(first_mode)
+    }
+
+    fn write_performance_mode(&self, mode: PerformanceMode) -> Result<(), io::Error> {
+        let value = mode.to_string(); // "balanced" or "performance"
+
+        info!("Writing performance mode: {:?} (value: {})", mode, value);
+
+        write_sysfs_value(&self.path(PERFORMANCE_PROFILE_PATH), &value)?;
+
+        info!("Successfully wrote performance mode: {:?}", mode);
+        Ok(())
+    }
+
+    fn read_performance_mode(&self) -> Result<PerformanceMode, io::Error> {
+        read_sysfs_value(&self.path(PERFORMANCE_PROFILE_PATH))?
+            .parse::<PerformanceMode>()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    fn read_performance_choices(&self) -> Result<Vec<PerformanceMode>, io::Error> {
+        let choices = read_sysfs_value(&self.path(PERFORMANCE_CHOICES_PATH))?;
+        Ok(choices
+            .split_whitespace()
+            .filter_map(|choice| match choice.parse::<PerformanceMode>() {
+                Ok(mode) => Some(mode),
+                Err(_) => {
+                    warn!("Ignoring unknown platform profile choice: {}", choice);
+                    None
+                }
+            })
+            .collect())
+    }
+
+    #[instrument(level = "debug")]
+    fn read_temperatures(&self) -> Result<Vec<SensorReading>, io::Error> {
+        let mut readings = Vec::new();
+
+        for path in self.glob(TEMP_SENSOR_PATH)? {
+            let Ok(temp) = read_sysfs_value(&path).and_then(|value| {
+                value
+                    .parse::<i32>()
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            }) else {
+                continue;
+            };
+            let name = read_sysfs_value(&path.with_file_name("type"))
+                .unwrap_or_else(|_| path.parent().unwrap_or(&path).display().to_string());
+            readings.push(SensorReading {
+                kind: SensorKind::ThermalZone,
+                name,
+                label: None,
+                temp,
+            });
+        }
+
+        for path in self.glob(HWMON_TEMP_SENSOR_PATH)? {
+            let Ok(temp) = read_sysfs_value(&path).and_then(|value| {
+                value
+                    .parse::<i32>()
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            }) else {
+                continue;
+            };
+            let name = read_sysfs_value(&path.with_file_name("name"))
+                .unwrap_or_else(|_| path.parent().unwrap_or(&path).display().to_string());
+            let label = path
+                .file_name()
+                .and_then(|file| file.to_str())
+                .and_then(|file| file.strip_suffix("_input"))
+                .and_then(|prefix| {
+                    read_sysfs_value(&path.with_file_name(format!("{}_label", prefix))).ok()
+                });
+            readings.push(SensorReading {
+                kind: SensorKind::Hwmon,
+                name,
+                label,
+                temp,
+            });
+        }
+
+        if readings.is_empty() {
+            error!("Failed to read temperature from any sensor");
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "No temperature sensor found",
+            ));
+        }
+
+        Ok(readings)
+    }
+
+    #[instrument(level = "debug")]
+    fn read_fan_speeds(&self) -> Result<Vec<u32>, io::Error> {
+        let paths = self.glob(FAN_SPEED_PATH)?;
+
+        if paths.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "No fan tachometer found",
+            ));
+        }
+
+        let speeds = paths
+            .iter()
+            .map(|path| {
+                let contents = fs::read_to_string(path)?;
+                contents.trim().parse::<u32>().map_err(|e| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Invalid fan speed in {:?}: {}", path, e),
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        debug!("Fan speeds read: {:?} RPM", speeds);
+        Ok(speeds)
+    }
+
+    fn read_keyboard_backlight(&self) -> Result<KeyboardBacklight, io::Error> {
+        let path = self.keyboard_backlight_path()?;
+        let read_value = |name: &str| {
+            read_sysfs_value(&path.join(name))?
+                .parse::<u32>()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        };
+        Ok(KeyboardBacklight {
+            brightness: read_value("brightness")?,
+            max_brightness: read_value("max_brightness")?,
+        })
+    }
+
+    fn write_keyboard_backlight(&self, brightness: u32) -> Result<(), io::Error> {
+        let path = self.keyboard_backlight_path()?;
+        info!("Writing keyboard backlight brightness: {}", brightness);
+        write_sysfs_value(&path.join("brightness"), &brightness.to_string())
+    }
+
+    fn read_battery(&self) -> Result<BatteryStatus, io::Error> {
+        let path = self
+            .glob(BATTERY_PATH)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No battery found"))?;
+        let read_percent = |name: &str| {
+            read_sysfs_value(&path.join(name))
+                .ok()
+                .and_then(|value| value.parse::<u8>().ok())
+        };
+
+        Ok(BatteryStatus {
+            name: path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            capacity: read_percent("capacity"),
+            charge_limit: read_percent("charge_control_end_threshold").map(|end| ChargeLimit {
+                start: read_percent("charge_control_start_threshold"),
+                end,
+            }),
+        })
+    }
+
+    fn write_charge_limit(&self, limit: ChargeLimit) -> Result<(), io::Error> {
+        let batteries: Vec<PathBuf> = self
+            .glob(BATTERY_PATH)?
+            .into_iter()
+            .filter(|path| path.join("charge_control_end_threshold").exists())
+            .collect();
+
+        if batteries.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "No battery with charge_control_end_threshold found",
+            ));
+        }
+
+        for battery in batteries {
+            let start_path = battery.join("charge_control_start_threshold");
+            let end_path = battery.join("charge_control_end_threshold");
+            info!("Writing charge limit {} to {:?}", limit, battery);
+
+            let read_percent = |path: &Path| {
+                read_sysfs_value(path)?
+                    .parse::<u8>()
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            };
+
+            let Some(start) = limit.start else {
+                // The kernel rejects end <= start, an end below the current start
+                // moves the start down first keeping the gap between them
+                let current_start = match start_path.exists() {
+                    true => Some(read_percent(&start_path)?),
+                    false => None,
+                };
+                match current_start {
+                    Some(current_start) if current_start >= limit.end => {
+                        let gap = read_percent(&end_path)?
+                            .saturating_sub(current_start)
+                            .max(1);
+                        let start = limit.end.saturating_sub(gap);
+                        info!("Lowering charge start threshold to {}%", start);
+                        write_sysfs_value(&start_path, &start.to_string())?;
+                        write_sysfs_value(&end_path, &limit.end.to_string())?;
+                    }
+                    _ => write_sysfs_value(&end_path, &limit.end.to_string())?,
+                }
+                continue;
+            };
+            if !start_path.exists() {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "Battery has no charge_control_start_threshold",
+                ));
+            }
+
+            // The kernel rejects start >= end, so order the writes to keep that true in between
+            let current_end = read_percent(&end_path)?;
+            if start < current_end {
+                write_sysfs_value(&start_path, &start.to_string())?;
+                write_sysfs_value(&end_path, &limit.end.to_string())?;
+            } else {
+                write_sysfs_value(&end_path, &limit.end.to_string())?;
+                write_sysfs_value(&start_path, &start.to_string())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_power_source(&self) -> Result<PowerSource, io::Error> {
+        // Some batteries expose `online` too, only look at external supplies (Mains, USB)
+        let online: Vec<bool> = self
+            .glob(POWER_SUPPLY_ONLINE_PATH)?
+            .iter()
+            .filter(|path| {
+                read_sysfs_value(&path.with_file_name("type"))
+                    .map(|kind| kind != "Battery")
+                    .unwrap_or(true)
+            })
+            .filter_map(|path| read_sysfs_value(path).ok())
+            .map(|value| value == "1")
+            .collect();
+
+        if online.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "No external power supply found",
+            ));
+        }
+
+        if online.contains(&true) {
+            Ok(PowerSource::Ac)
+        } else {
+            Ok(PowerSource::Battery)
+        }
+    }
+
+    fn read_cpu_policy(&self) -> Result<CpuPolicy, io::Error> {
+        let first_value = |pattern: &str| -> Result<Option<String>, io::Error> {
+            Ok(self
+                .glob(pattern)?
+                .first()
+                .and_then(|path| read_sysfs_value(path).ok()))
+        };
+
+        let no_turbo = read_sysfs_value(&self.path(INTEL_NO_TURBO_PATH));
+        let boost = read_sysfs_value(&self.path(CPUFREQ_BOOST_PATH));
+        let turbo = match (no_turbo, boost) {
+            (Ok(no_turbo), _) => Some(no_turbo == "0"),
+            (_, Ok(boost)) => Some(boost == "1"),
+            _ => None,
+        };
+
+        let policy = CpuPolicy {
+            governor: first_value(CPU_GOVERNOR_PATH)?,
+            epp: first_value(CPU_EPP_PATH)?,
+            turbo,
+        };
+        if policy == CpuPolicy::default() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "No cpufreq attributes found",
+            ));
+        }
+        Ok(policy)
+    }
+
+    fn write_cpu_policy(&self, policy: &CpuPolicy) -> Result<(), io::Error> {
+        info!("Writing CPU policy: {}", policy);
+        let mut failures = Vec::new();
+
+        // Governor first, intel_pstate only accepts some EPP values under powersave
+        let writes = [
+            (CPU_GOVERNOR_PATH, policy.governor.as_deref()),
+            (CPU_EPP_PATH, policy.epp.as_deref()),
+        ];
+        for (pattern, value) in writes {
+            let Some(value) = value else {
+                continue;
+            };
+            let paths = self.glob(pattern)?;
+            if paths.is_empty() {
+                failures.push(format!("{}: not found", pattern));
+            }
+            for path in paths {
+                if let Err(e) = write_sysfs_value(&path, value) {
+                    failures.push(format!("{:?}: {}", path, e));
+                }
+            }
+        }
+
+        if let Some(turbo) = policy.turbo {
+            let no_turbo_path = self.path(INTEL_NO_TURBO_PATH);
+            let result = if no_turbo_path.exists() {
+                write_sysfs_value(&no_turbo_path, if turbo { "0" } else { "1" })
+            } else {
+                write_sysfs_value(
+                    &self.path(CPUFREQ_BOOST_PATH),
+                    if turbo { "1" } else { "0" },
+                )
+            };
+            if let Err(e) = result {
+                failures.push(format!("turbo: {}", e));
+            }
+        }
+
+        if !failures.is_empty() {
+            return Err(io::Error::other(format!(
+                "Failed to write CPU policy: {}",
+                failures.join("; ")
+            )));
+        }
+        Ok(())
+    }
+
+    fn read_cpu_max_freqs(&self) -> Result<Vec<u32>, io::Error> {
+        let paths = self.glob(CPU_MAX_FREQ_PATH)?;
+        if paths.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "No scaling_max_freq attributes found",
+            ));
+        }
+        paths
+            .iter()
+            .map(|path| {
+                read_sysfs_value(path)?.parse::<u32>().map_err(|e| {
+                    io::Error::new(io::ErrorKind::InvalidData, format!("{:?}: {}", path, e))
+                })
+            })
+            .collect()
+    }
+
+    fn write_cpu_max_freqs(&self, freqs: &[u32]) -> Result<(), io::Error> {
+        let paths = self.glob(CPU_MAX_FREQ_PATH)?;
+        if paths.len() != freqs.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Expected {} CPU frequency limits, got {}",
+                    paths.len(),
+                    freqs.len()
+                ),
+            ));
+        }
+
+        let failures: Vec<String> = paths
+            .iter()
+            .zip(freqs)
+            .filter_map(|(path, freq)| {
+                write_sysfs_value(path, &freq.to_string())
+                    .err()
+                    .map(|e| format!("{:?}: {}", path, e))
+            })
+            .collect();
+        if !failures.is_empty() {
+            return Err(io::Error::other(format!(
+                "Failed to write CPU frequency limits: {}",
+                failures.join("; ")
+            )));
+        }
+        Ok(())
+    }
+
+    fn read_keyboard_activity(&self) -> Result<u64, io::Error> {
+        // The built-in keyboard sits behind the i8042 controller on IRQ 1, the
+        // touchpad shares the controller on IRQ 12 and must not count
+        let interrupts = fs::read_to_string(self.path(INTERRUPTS_PATH))?;
+        let line = interrupts
+            .lines()
+            .find(|line| {
+                line.trim_start().starts_with(I8042_KEYBOARD_IRQ) && line.contains("i8042")
+            })
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, "No i8042 keyboard interrupt found")
+            })?;
+
+        // The line is `IRQ: count-per-cpu... chip ... i8042`
+        Ok(line
+            .split_whitespace()
+            .skip(1)
+            .map_while(|count| count.parse::<u64>().ok())
+            .sum())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const HWMON: &str = "sys/devices/platform/hp-wmi/hwmon/hwmon3";
+
+    /// Fake sysfs tree with two hp-wmi fan channels under BIOS control
+    pub(crate) fn fake_sysfs() -> TempDir {
+        let root = tempfile::tempdir().unwrap();
+        let hwmon = root.path().join(HWMON);
+        fs::create_dir_all(&hwmon).unwrap();
+        for pwm in ["pwm1", "pwm2"] {
+            fs::write(hwmon.join(format!("{}_enable", pwm)), "2\n").unwrap();
+            fs::write(hwmon.join(pwm), "0\n").unwrap();
+        }
+        let acpi = root.path().join("sys/firmware/acpi");
+        fs::create_dir_all(&acpi).unwrap();
+        fs::write(acpi.join("platform_profile"), "balanced\n").unwrap();
+        fs::write(
+            acpi.join("platform_profile_choices"),
+            "low-power balanced performance bogus\n",
+        )
+        .unwrap();
+        root
+    }
+
+    fn read(root: &TempDir, relative: &str) -> String {
+        read_sysfs_value(&root.path().join(relative)).unwrap()
+    }
+
+    fn backend(root: &TempDir, filter: &[&str]) -> SysfsBackend {
+        SysfsBackend::new(
+            root.path(),
+            filter.iter().map(|channel| channel.to_string()).collect(),
+        )
+    }
+
+    #[test]
+    fn fan_channels_finds_every_channel() {
+        let root = fake_sysfs();
+        let channels = backend(&root, &[]).fan_channels().unwrap();
+        assert_eq!(channels, ["hwmon3/pwm1", "hwmon3/pwm2"]);
+    }
+
+    #[test]
+    fn fan_channel_filter_matches_bare_and_qualified_names() {
+        let root = fake_sysfs();
+        assert_eq!(
+            backend(&root, &["pwm2"]).fan_channels().unwrap(),
+            ["hwmon3/pwm2"]
+        );
+        assert_eq!(
+            backend(&root, &["hwmon3/pwm1"]).fan_channels().unwrap(),
+            ["hwmon3/pwm1"]
+        );
+        let err = backend(&root, &["pwm9"]).fan_channels().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn write_fan_mode_writes_every_channel() {
+        let root = fake_sysfs();
+        let backend = backend(&root, &[]);
+        let report = backend.write_fan_mode(HardwareFanMode::Max).unwrap();
+        assert!(report.channels.iter().all(|(_, result)| result.is_ok()));
+        assert_eq!(read(&root, &format!("{}/pwm1_enable", HWMON)), "0");
+        assert_eq!(read(&root, &format!("{}/pwm2_enable", HWMON)), "0");
+        assert_eq!(backend.read_fan_mode().unwrap(), HardwareFanMode::Max);
+
+        backend.write_fan_mode(HardwareFanMode::Bios).unwrap();
+        assert_eq!(backend.read_fan_mode().unwrap(), HardwareFanMode::Bios);
+    }
+
+    #[test]
+    fn write_fan_mode_only_touches_filtered_channels() {
+        let root = fake_sysfs();
+        backend(&root, &["pwm1"])
+            .write_fan_mode(HardwareFanMode::Max)
+            .unwrap();
+        assert_eq!(read(&root, &format!("{}/pwm1_enable", HWMON)), "0");
+        assert_eq!(read(&root, &format!("{}/pwm2_enable", HWMON)), "2");
+    }
+
+    #[test]
+    fn manual_mode_round_trips_through_pwm_duty() {
+        let root = fake_sysfs();
+        let backend = backend(&root, &[]);
+        assert!(backend.supports_manual_duty());
+        backend.write_fan_mode(HardwareFanMode::Manual(40)).unwrap();
+        assert_eq!(read(&root, &format!("{}/pwm1_enable", HWMON)), "1");
+        assert_eq!(read(&root, &format!("{}/pwm1", HWMON)), "102");
+        assert_eq!(
+            backend.read_fan_mode().unwrap(),
+            HardwareFanMode::Manual(40)
+        );
+    }
+
+    #[test]
+    fn failed_duty_write_returns_channel_to_bios() {
+        let root = fake_sysfs();
+        let duty = root.path().join(HWMON).join("pwm1");
+        fs::remove_file(&duty).unwrap();
+        fs::create_dir(&duty).unwrap();
+
+        let report = backend(&root, &[])
+            .write_fan_mode(HardwareFanMode::Manual(40))
+            .unwrap();
+        assert!(report.channels[0].1.is_err());
+        assert!(report.channels[1].1.is_ok());
+        assert_eq!(read(&root, &format!("{}/pwm1_enable", HWMON)), "2");
+        assert_eq!(read(&root, &format!("{}/pwm2_enable", HWMON)), "1");
+    }
+
+    #[test]
+    fn manual_duty_needs_every_duty_attribute() {
+        let root = fake_sysfs();
+        assert!(
+            !backend(&root, &[])
+                .with_manual_duty(false)
+                .supports_manual_duty()
+        );
+        fs::remove_file(root.path().join(HWMON).join("pwm2")).unwrap();
+        assert!(!backend(&root, &[]).supports_manual_duty());
+        assert!(backend(&root, &["pwm1"]).supports_manual_duty());
+    }
+
+    #[test]
+    fn read_fan_mode_rejects_unknown_values() {
+        let root = fake_sysfs();
+        fs::write(root.path().join(HWMON).join("pwm1_enable"), "7\n").unwrap();
+        let err = backend(&root, &[]).read_fan_mode().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn performance_mode_and_choices() {
+        let root = fake_sysfs();
+        let backend = backend(&root, &[]);
+        assert_eq!(
+            backend.read_performance_choices().unwrap(),
+            [
+                PerformanceMode::LowPower,
+                PerformanceMode::Balanced,
+                PerformanceMode::Performance
+            ]
+        );
+        assert_eq!(
+            backend.read_performance_mode().unwrap(),
+            PerformanceMode::Balanced
+        );
+        backend
+            .write_performance_mode(PerformanceMode::LowPower)
+            .unwrap();
+        assert_eq!(
+            read(&root, "sys/firmware/acpi/platform_profile"),
+            "low-power"
+        );
+    }
+
+    #[test]
+    fn keyboard_activity_ignores_the_touchpad_irq() {
+        let root = fake_sysfs();
+        fs::create_dir_all(root.path().join("proc")).unwrap();
+        let interrupts = root.path().join(INTERRUPTS_PATH);
+        fs::write(
+            &interrupts,
+            "           CPU0       CPU1\n  \
+               1:         10          5  IR-IO-APIC    1-edge      i8042\n  \
+               8:          0          0  IR-IO-APIC    8-edge      rtc0\n \
+              12:        400        300  IR-IO-APIC   12-edge      i8042\n",
+        )
+        .unwrap();
+        let backend = backend(&root, &[]);
+        assert_eq!(backend.read_keyboard_activity().unwrap(), 15);
+
+        fs::write(&interrupts, "  12:  400  IR-IO-APIC   12-edge      i8042\n").unwrap();
+        let err = backend.read_keyboard_activity().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    /// Adds a battery with the given thresholds to the fake tree
+    fn fake_battery(root: &TempDir, start: u8, end: u8) {
+        let battery = root.path().join("sys/class/power_supply/BAT0");
+        fs::create_dir_all(&battery).unwrap();
+        fs::write(battery.join("capacity"), "60\n").unwrap();
+        fs::write(
+            battery.join("charge_control_start_threshold"),
+            format!("{}\n", start),
+        )
+        .unwrap();
+        fs::write(
+            battery.join("charge_control_end_threshold"),
+            format!("{}\n", end),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn end_only_limit_moves_a_higher_start_down() {
+        let root = fake_sysfs();
+        fake_battery(&root, 75, 80);
+        let backend = backend(&root, &[]);
+        backend.write_charge_limit("70".parse().unwrap()).unwrap();
+        assert_eq!(
+            backend.read_battery().unwrap().charge_limit,
+            Some(ChargeLimit {
+                start: Some(65),
+                end: 70
+            })
+        );
+
+        backend.write_charge_limit("90".parse().unwrap()).unwrap();
+        assert_eq!(
+            read(
+                &root,
+                "sys/class/power_supply/BAT0/charge_control_start_threshold"
+            ),
+            "65"
+        );
+        assert_eq!(
+            read(
+                &root,
+                "sys/class/power_supply/BAT0/charge_control_end_threshold"
+            ),
+            "90"
+        );
+    }
+
+    #[test]
+    fn zero_start_threshold_reads_back() {
+        let root = fake_sysfs();
+        fake_battery(&root, 0, 100);
+        let limit = backend(&root, &[])
+            .read_battery()
+            .unwrap()
+            .charge_limit
+            .unwrap();
+        assert_eq!(limit.to_string(), "0-100%");
+        assert_eq!(limit.to_string().parse::<ChargeLimit>(), Ok(limit));
+    }
+
+    #[test]
+    fn missing_tree_reports_not_found() {
+        let root = tempfile::tempdir().unwrap();
+        let backend = backend(&root, &[]);
+        assert!(!backend.platform_device_present());
+        assert!(backend.fan_channels().is_err());
+        assert!(backend.read_performance_mode().is_err());
+    }
+}
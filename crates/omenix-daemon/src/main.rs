@@ -1,21 +1,57 @@
+mod cpu;
+mod critical;
+mod curve;
+mod doctor;
+mod filter;
+mod hardware;
+mod persist;
+mod pid;
+mod power;
+mod ppd;
+mod quirks;
+mod sensors;
+mod shutdown;
+mod trend;
+
+use clap::parser::ValueSource;
 use clap::{CommandFactory, Parser};
 use clap_config::ClapConfig;
+use std::collections::VecDeque;
 use std::fs;
 use std::io::{self, Read, Write};
 use std::os::unix::net::UnixStream;
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, TryLockError};
 use std::thread;
 use std::time::{Duration, Instant};
-use tracing::{debug, error, info, instrument, warn};
-
+use tracing::{debug, error, info, warn};
+
+use crate::cpu::{CpuPolicy, CpuPolicyMapping};
+use crate::critical::CriticalStage;
+use crate::curve::{CurvePoint, FanCurve};
+use crate::filter::{TempFilter, TempSmoother};
+use crate::hardware::{FanWriteReport, HardwareBackend, SysfsBackend};
+use crate::persist::StateStore;
+use crate::pid::PidController;
+use crate::power::{PowerProfile, PowerSource};
+use crate::quirks::BoardQuirk;
+use crate::sensors::{SensorSelector, TempAggregation};
+use crate::shutdown::{ExitRestore, ShutdownFanMode};
+use crate::trend::TempTrend;
 use omenix_lib::client::DAEMON_SOCKET_PATH;
-use omenix_lib::types::{FanMode, HardwareFanMode, PerformanceMode};
+use omenix_lib::types::{
+    ChargeLimit, FanMode, HardwareFanMode, KeyboardBacklight, PerformanceMode,
+};
 
-const TEMP_SENSOR_PATH: &str = "/sys/class/thermal/thermal_zone*/temp";
-const FAN_CONTROL_PATH: &str = "/sys/devices/platform/hp-wmi/hwmon/hwmon*/pwm1_enable";
-const PERFORMANCE_PROFILE_PATH: &str = "/sys/firmware/acpi/platform_profile";
 const CONFIG_FILE_PATH: &str = "/etc/omenix-daemon.yaml";
+const CHARGE_LIMIT_KEY: &str = "charge_limit";
+const FAN_MODE_KEY: &str = "fan_mode";
+const PERFORMANCE_MODE_KEY: &str = "performance_mode";
+const KBD_BACKLIGHT_KEY: &str = "kbd_backlight";
+const POWER_SOURCE_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+const EXTERNAL_CHANGE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// Degrees below `temp_critical` the temperature has to fall before critical actions are undone
+const CRITICAL_RECOVERY_MARGIN: i32 = 5;
 
 #[derive(ClapConfig, Parser, Debug, Clone)]
 pub struct AppConfig {
@@ -31,187 +67,567 @@ pub struct AppConfig {
     /// Number of consecutive low temperature readings to switch back to BIOS control when fans are on max
     #[clap(long, default_value = "3")]
     consecutive_low_temp_limit: u32,
+    /// Temperature in Celsius that counts as critical once the fans are already at max (disabled when unset)
+    #[clap(long)]
+    temp_critical: Option<i32>,
+    /// Seconds between escalation steps while the temperature stays critical: power-saver profile, CPU frequency cap, then `critical_command`
+    #[clap(long, default_value = "30")]
+    temp_critical_duration: u64,
+    /// CPU frequency cap in MHz applied at critical temperature (not capped when unset)
+    #[clap(long)]
+    critical_cpu_max_freq: Option<u32>,
+    /// Shell command run as the last escalation step, e.g. `systemctl suspend`
+    #[clap(long)]
+    critical_command: Option<String>,
+    /// Consecutive failed or implausible temperature readings before the fans are forced to max in Auto and Curve mode
+    #[clap(long, default_value = "3")]
+    sensor_failure_limit: u32,
+    /// Lowest temperature in Celsius accepted as a real reading
+    #[clap(long, default_value = "1")]
+    temp_plausible_min: i32,
+    /// Highest temperature in Celsius accepted as a real reading
+    #[clap(long, default_value = "120")]
+    temp_plausible_max: i32,
+    /// Minimum seconds Auto mode keeps the fans on max before switching back to BIOS control
+    #[clap(long, default_value = "0")]
+    auto_min_max_time: u64,
+    /// Minimum seconds Auto mode leaves BIOS control in charge before switching to max fans
+    #[clap(long, default_value = "0")]
+    auto_min_bios_time: u64,
+    /// Seconds Auto mode waits after the temperature is consistently low before switching back to BIOS control
+    #[clap(long, default_value = "0")]
+    auto_spin_down_delay: u64,
+    /// Maximum number of BIOS/Max switches within `auto_transition_window`, the fans stay on max once reached (unlimited when unset)
+    #[clap(long)]
+    auto_max_transitions: Option<u32>,
+    /// Seconds over which `auto_max_transitions` is counted
+    #[clap(long, default_value = "600")]
+    auto_transition_window: u64,
     /// Interval in seconds to check temperature
     #[clap(long, default_value = "5")]
     temp_check_interval: u64,
     /// Interval in seconds to rewrite max fan mode in Max mode
     #[clap(long, default_value = None)]
     max_fan_write_interval: Option<u64>,
+    /// Root directory that sysfs paths are resolved against (point at a fake tree for testing)
+    #[clap(long, default_value = "/")]
+    sysfs_root: PathBuf,
+    /// Fan control channels to write, e.g. `pwm1` or `hwmon3/pwm2` (all channels when empty)
+    #[clap(long, value_delimiter = ',')]
+    fan_channels: Vec<String>,
+    /// Fan curve points as temp:duty (Celsius:percent) used in Curve mode
+    #[clap(
+        long,
+        value_delimiter = ',',
+        default_value = "50:20,60:35,70:55,80:80,90:100"
+    )]
+    fan_curve: Vec<CurvePoint>,
+    /// Temperature sensors to use by thermal zone type or hwmon name/label, as name or name:weight (all thermal zones when empty)
+    #[clap(long, value_delimiter = ',')]
+    temp_sensors: Vec<SensorSelector>,
+    /// How readings from the selected sensors are combined
+    #[clap(long, value_enum, default_value = "max")]
+    temp_aggregation: TempAggregation,
+    /// Smoothing applied to the temperature before Auto, Curve and PID act on it
+    #[clap(long, value_enum, default_value = "none")]
+    temp_filter: TempFilter,
+    /// Weight of the newest reading in the exponential moving average (0-1)
+    #[clap(long, default_value = "0.3")]
+    temp_filter_alpha: f64,
+    /// Number of readings the rolling median is taken over
+    #[clap(long, default_value = "5")]
+    temp_filter_window: usize,
+    /// Switch to max fans (pid_max_duty under PID control) in Auto mode as soon as the temperature rises faster than this many degrees Celsius per second (disabled when unset)
+    #[clap(long)]
+    temp_slope_limit: Option<f64>,
+    /// Seconds of readings the temperature slope is computed over
+    #[clap(long, default_value = "10")]
+    temp_slope_window: u64,
+    /// Target temperature in Celsius for PID fan control in Auto mode on boards with writable PWM duty (threshold switching when unset)
+    #[clap(long)]
+    pid_setpoint: Option<i32>,
+    /// PID proportional gain in percent duty per degree Celsius above the setpoint
+    #[clap(long, default_value = "4.0")]
+    pid_kp: f64,
+    /// PID integral gain in percent duty per degree Celsius second
+    #[clap(long, default_value = "0.2")]
+    pid_ki: f64,
+    /// PID derivative gain in percent duty per degree Celsius per second
+    #[clap(long, default_value = "1.0")]
+    pid_kd: f64,
+    /// Lowest fan duty in percent the PID controller outputs
+    #[clap(long, default_value = "20")]
+    pid_min_duty: u8,
+    /// Highest fan duty in percent the PID controller outputs
+    #[clap(long, default_value = "100")]
+    pid_max_duty: u8,
+    /// Fan mode forced when the daemon starts (the last mode chosen by the user, or auto, when unset)
+    #[clap(long)]
+    startup_fan_mode: Option<FanMode>,
+    /// Platform profile forced when the daemon starts (the last profile chosen by the user, or the current profile, when unset)
+    #[clap(long)]
+    startup_performance_mode: Option<PerformanceMode>,
+    /// Fan mode written back when the daemon stops or crashes, bios hands the fans to the firmware and boot restores the mode found at startup
+    #[clap(long, value_enum, default_value = "bios")]
+    shutdown_fan_mode: ShutdownFanMode,
+    /// Turn the keyboard backlight off after this many seconds without keyboard input (disabled when unset)
+    #[clap(long)]
+    kbd_backlight_idle_timeout: Option<u64>,
+    /// Directory for settings that must survive a reboot (e.g. the battery charge limit)
+    #[clap(long, default_value = "/var/lib/omenix")]
+    state_dir: PathBuf,
+    /// Fan and performance mode applied when switching to AC power, e.g. `auto:performance` (at startup only when no startup or saved mode is in effect)
+    #[clap(long)]
+    ac_profile: Option<PowerProfile>,
+    /// Fan and performance mode applied when switching to battery, e.g. `bios:power-saver`
+    #[clap(long)]
+    battery_profile: Option<PowerProfile>,
+    /// CPU policy per performance mode as mode:governor:epp:turbo, e.g. `power-saver:powersave:power:off` (empty fields are left alone)
+    #[clap(long, value_delimiter = ',')]
+    cpu_policies: Vec<CpuPolicyMapping>,
+    /// Serve net.hadess.PowerProfiles on this D-Bus bus, `system` or an address like `unix:path=/run/dbus/test` (disabled when unset, conflicts with power-profiles-daemon)
+    #[clap(long)]
+    power_profiles_dbus: Option<String>,
+}
+
+/// Who made the last change to a hardware setting
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChangeSource {
+    Daemon,   // Set through the daemon (socket, startup, power profiles)
+    External, // Omen key, power-profiles-daemon or a manual sysfs write
+}
+
+impl std::fmt::Display for ChangeSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChangeSource::Daemon => write!(f, "daemon"),
+            ChangeSource::External => write!(f, "external"),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct DaemonState {
     pub user_mode: FanMode,
     pub actual_mode: HardwareFanMode,
-    pub performance_mode: PerformanceMode,
+    /// None until `platform_profile` has been read or written
+    pub performance_mode: Option<PerformanceMode>,
+    pub performance_choices: Vec<PerformanceMode>,
+    pub fan_mode_source: ChangeSource,
+    pub performance_source: ChangeSource,
+    /// Hardware state read back when the daemon started
+    pub boot_fan_mode: Option<HardwareFanMode>,
+    pub boot_performance_mode: Option<PerformanceMode>,
+    /// DMI board name and the quirk entry matched for it
+    pub board_name: Option<String>,
+    pub quirk: Option<&'static BoardQuirk>,
     pub last_fan_write: Option<Instant>,
+    /// Number of times the firmware changed the fan mode behind our back
+    pub fan_mode_resets: u32,
     pub consecutive_high_temps: u32,
     pub consecutive_low_temps: u32,
+    /// Last BIOS/Max switch made by Auto mode and the switches within `auto_transition_window`
+    pub auto_switched_at: Option<Instant>,
+    pub auto_switches: VecDeque<Instant>,
+    /// When the spin-down delay started and until when Auto mode holds the current mode
+    pub spin_down_since: Option<Instant>,
+    pub auto_hold_until: Option<Instant>,
     pub temp_monitoring_active: bool,
+    /// Filtered temperature the monitor acts on and the reading it came from
     pub current_temp: Option<i32>,
+    pub raw_temp: Option<i32>,
+    pub temp_source: Option<String>,
+    /// Temperature change in °C per second over `temp_slope_window`
+    pub temp_slope: Option<f64>,
+    /// Consecutive failed temperature readings and the failsafe error they raised
+    pub sensor_failures: u32,
+    pub sensor_error: Option<String>,
+    /// Critical temperature tracking: when it started, the last escalation
+    /// step taken and what to put back once the temperature recovers
+    pub critical_since: Option<Instant>,
+    pub critical_stage: Option<CriticalStage>,
+    pub critical_alert: Option<String>,
+    /// Performance mode before the power-saver step and the mode it applied
+    pub critical_saved_performance: Option<(PerformanceMode, PerformanceMode)>,
+    pub critical_saved_cpu_freqs: Option<Vec<u32>>,
+    pub fan_speeds: Vec<u32>,
+    /// Brightness to restore once the keyboard is used again after an idle auto-off
+    pub kbd_backlight_saved: Option<u32>,
+    /// CPU policy read at startup, restored on exit once a policy was applied
+    pub cpu_policy_boot: Option<CpuPolicy>,
+    pub cpu_policy_applied: bool,
+    /// Last power source seen by the power source monitor
+    pub power_source: Option<PowerSource>,
+    /// Set once the exit restore started, fan writes are refused from then on
+    pub shutting_down: bool,
     pub config: AppConfig,
+    pub hardware: Arc<dyn HardwareBackend>,
 }
 
 impl DaemonState {
-    pub fn new(config: &AppConfig) -> Self {
+    /// Reads the current hardware state instead of assuming defaults
+    pub fn new(
+        config: &AppConfig,
+        hardware: Arc<dyn HardwareBackend>,
+        board_name: Option<String>,
+        quirk: Option<&'static BoardQuirk>,
+    ) -> Self {
+        let boot_fan_mode = match hardware.read_fan_mode() {
+            Ok(mode) => {
+                info!("Fan mode at startup: {:?}", mode);
+                Some(mode)
+            }
+            Err(e) => {
+                warn!("Failed to read fan mode at startup: {}", e);
+                None
+            }
+        };
+        let boot_performance_mode = match hardware.read_performance_mode() {
+            Ok(mode) => {
+                info!("Platform profile at startup: {}", mode);
+                Some(mode)
+            }
+            Err(e) => {
+                warn!("Failed to read platform profile at startup: {}", e);
+                None
+            }
+        };
+        let performance_choices = match hardware.read_performance_choices() {
+            Ok(choices) => {
+                info!("Supported platform profiles: {:?}", choices);
+                choices
+            }
+            Err(e) => {
+                warn!("Failed to read platform profile choices: {}", e);
+                Vec::new()
+            }
+        };
+        let performance_choices = match quirk {
+            Some(quirk) => quirk.filter_performance_choices(performance_choices),
+            None => performance_choices,
+        };
+
+        let cpu_policy_boot = if config.cpu_policies.is_empty() {
+            None
+        } else {
+            match hardware.read_cpu_policy() {
+                Ok(policy) => {
+                    info!("CPU policy at startup: {}", policy);
+                    Some(policy)
+                }
+                Err(e) => {
+                    warn!("Failed to read CPU policy at startup: {}", e);
+                    None
+                }
+            }
+        };
+
         let state = Self {
             user_mode: FanMode::Auto,
-            actual_mode: HardwareFanMode::Bios,
-            performance_mode: PerformanceMode::Performance,
+            actual_mode: boot_fan_mode.unwrap_or(HardwareFanMode::Bios),
+            performance_mode: boot_performance_mode,
+            performance_choices,
+            fan_mode_source: ChangeSource::Daemon,
+            performance_source: ChangeSource::Daemon,
+            boot_fan_mode,
+            boot_performance_mode,
+            board_name,
+            quirk,
             last_fan_write: None,
+            fan_mode_resets: 0,
             consecutive_high_temps: 0,
             consecutive_low_temps: 0,
+            auto_switched_at: None,
+            auto_switches: VecDeque::new(),
+            spin_down_since: None,
+            auto_hold_until: None,
             temp_monitoring_active: false,
             current_temp: None,
+            raw_temp: None,
+            temp_source: None,
+            temp_slope: None,
+            sensor_failures: 0,
+            sensor_error: None,
+            critical_since: None,
+            critical_stage: None,
+            critical_alert: None,
+            critical_saved_performance: None,
+            critical_saved_cpu_freqs: None,
+            fan_speeds: Vec::new(),
+            kbd_backlight_saved: None,
+            power_source: None,
+            shutting_down: false,
+            cpu_policy_boot,
+            cpu_policy_applied: false,
             config: config.clone(),
+            hardware,
         };
         info!("DaemonState initialized: {:?}", state);
         state
     }
 }
 
-#[instrument(level = "debug")]
-fn write_fan_mode(mode: HardwareFanMode) -> Result<(), io::Error> {
-    let value = match mode {
-        HardwareFanMode::Max => "0",
-        HardwareFanMode::Bios => "2",
-    };
-
-    info!("Writing fan mode: {:?} (value: {})", mode, value);
-
-    // Find the actual fan control file
-    let paths: Vec<_> = glob::glob(FAN_CONTROL_PATH)
-        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?
-        .filter_map(Result::ok)
-        .collect();
-
-    if paths.is_empty() {
-        return Err(io::Error::new(
-            io::ErrorKind::NotFound,
-            "No fan control file found",
-        ));
-    }
-
-    let fan_path = &paths[0];
-    debug!("Writing to fan control file: {:?}", fan_path);
-
-    let mut file = fs::OpenOptions::new()
-        .write(true)
-        .truncate(true)
-        .open(fan_path)?;
-
-    file.write_all(value.as_bytes())?;
-    file.flush()?;
-
-    info!("Successfully wrote fan mode: {:?}", mode);
-    Ok(())
-}
-
-fn write_performance_mode(mode: PerformanceMode) -> Result<(), io::Error> {
-    let value = mode.to_string(); // "balanced" or "performance"
-
-    info!("Writing performance mode: {:?} (value: {})", mode, value);
-
-    let mut file = fs::OpenOptions::new()
-        .write(true)
-        .truncate(true)
-        .open(PERFORMANCE_PROFILE_PATH)?;
-
-    file.write_all(value.as_bytes())?;
-    file.flush()?;
-
-    info!("Successfully wrote performance mode: {:?}", mode);
-    Ok(())
-}
-
-#[instrument(level = "debug")]
-fn read_temperature() -> Result<i32, io::Error> {
-    let paths: Vec<_> = glob::glob(TEMP_SENSOR_PATH)
-        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?
-        .filter_map(Result::ok)
-        .collect();
-
-    if paths.is_empty() {
-        return Err(io::Error::new(
-            io::ErrorKind::NotFound,
-            "No temperature sensor found",
-        ));
-    }
-
-    let max_temp = paths
-        .iter()
-        .filter_map(|path| {
-            let mut file = fs::File::open(path).ok()?;
-            let mut contents = String::new();
-            file.read_to_string(&mut contents).ok()?;
-            contents.trim().parse::<i32>().ok()
-        })
-        .max()
-        .ok_or_else(|| {
-            error!("Failed to read temperature from any sensor");
-            io::Error::new(io::ErrorKind::InvalidData, "Failed to read temperature")
-        })?;
-
-    debug!("Max temperature read: {}°C", max_temp / 1000);
-    Ok(max_temp)
-}
-
 fn handle_client_request(request: &str, state: Arc<Mutex<DaemonState>>) -> Result<String, String> {
     let parts: Vec<&str> = request.split_whitespace().collect();
 
     match parts.as_slice() {
-        ["set", mode_str] => {
-            let mode = mode_str
-                .parse::<FanMode>()
-                .map_err(|_| "Invalid fan mode")?;
-            set_fan_mode(state, mode)?;
-            Ok(format!("Fan mode set to: {}", mode))
+        ["set", mode_parts @ ..] if !mode_parts.is_empty() => {
+            let mode = mode_parts.join(" ").parse::<FanMode>()?;
+            let report = set_fan_mode(state.clone(), mode)?;
+            save_user_setting(&state, FAN_MODE_KEY, &mode);
+            Ok(format!("Fan mode set to: {} ({})", mode, report))
         }
         ["set_performance", mode_str] => {
             let mode = mode_str
                 .parse::<PerformanceMode>()
                 .map_err(|_| "Invalid performance mode")?;
-            set_performance_mode(state, mode)?;
+            set_performance_mode(state.clone(), mode)?;
+            save_user_setting(&state, PERFORMANCE_MODE_KEY, &mode);
             Ok(format!("Performance mode set to: {}", mode))
         }
+        ["get_kbd_backlight"] => {
+            let hardware = state.lock().unwrap().hardware.clone();
+            let backlight = hardware
+                .read_keyboard_backlight()
+                .map_err(|e| format!("Failed to read keyboard backlight: {}", e))?;
+            Ok(format!("Keyboard: {}", backlight))
+        }
+        ["set_kbd_backlight", brightness_str] => {
+            let brightness = brightness_str
+                .parse::<u32>()
+                .map_err(|_| "Invalid keyboard backlight brightness")?;
+            let backlight = set_keyboard_backlight(state.clone(), brightness)?;
+            save_user_setting(&state, KBD_BACKLIGHT_KEY, &brightness);
+            Ok(format!("Keyboard backlight set to: {}", backlight))
+        }
+        ["get_charge_limit"] => {
+            let hardware = state.lock().unwrap().hardware.clone();
+            let battery = hardware
+                .read_battery()
+                .map_err(|e| format!("Failed to read battery: {}", e))?;
+            let limit = battery
+                .charge_limit
+                .ok_or("Battery does not support a charge limit")?;
+            Ok(format!("Limit: {}", limit))
+        }
+        ["set_charge_limit", limit_str] => {
+            let limit = limit_str.parse::<ChargeLimit>()?;
+            set_charge_limit(state, limit)?;
+            Ok(format!("Charge limit set to: {}", limit))
+        }
         ["status"] => {
             let state_guard = state.lock().unwrap();
             let temp_str = match state_guard.current_temp {
                 Some(temp) => format!("{}°C", temp / 1000),
                 None => "Unknown".to_string(),
             };
-            Ok(format!(
-                "Mode: {}, Actual: {:?}, Performance: {}, Temp: {}",
-                state_guard.user_mode,
-                state_guard.actual_mode,
-                state_guard.performance_mode,
-                temp_str
-            ))
+            let raw_temp_str = match state_guard.raw_temp {
+                Some(temp) => format!("{}°C", temp / 1000),
+                None => "Unknown".to_string(),
+            };
+            let slope_str = match state_guard.temp_slope {
+                Some(slope) => format!("{:+.1}°C/s", slope),
+                None => "Unknown".to_string(),
+            };
+            let profiles_str = if state_guard.performance_choices.is_empty() {
+                "Unknown".to_string()
+            } else {
+                let profiles: Vec<String> = state_guard
+                    .performance_choices
+                    .iter()
+                    .map(|mode| mode.to_string())
+                    .collect();
+                profiles.join("/")
+            };
+            let fans_str = if state_guard.fan_speeds.is_empty() {
+                "Unknown".to_string()
+            } else {
+                let speeds: Vec<String> = state_guard
+                    .fan_speeds
+                    .iter()
+                    .map(|rpm| rpm.to_string())
+                    .collect();
+                format!("{} RPM", speeds.join("/"))
+            };
+            let keyboard_str = match state_guard.hardware.read_keyboard_backlight() {
+                Ok(backlight) => backlight.to_string(),
+                Err(_) => "Unknown".to_string(),
+            };
+            let (battery_str, limit_str) = match state_guard.hardware.read_battery() {
+                Ok(battery) => (
+                    battery
+                        .capacity
+                        .map(|capacity| format!("{}%", capacity))
+                        .unwrap_or_else(|| "Unknown".to_string()),
+                    battery
+                        .charge_limit
+                        .map(|limit| limit.to_string())
+                        .unwrap_or_else(|| "Unknown".to_string()),
+                ),
+                Err(_) => ("Unknown".to_string(), "Unknown".to_string()),
+            };
+            let hold_str = match state_guard
+                .auto_hold_until
+                .map(|until| until.saturating_duration_since(Instant::now()))
+            {
+                Some(remaining) if !remaining.is_zero() => format!(
+                    "{:?} for {} more s",
+                    state_guard.actual_mode,
+                    remaining.as_secs().max(1)
+                ),
+                _ => "None".to_string(),
+            };
+            let boot_str = format!(
+                "{}/{}",
+                state_guard
+                    .boot_fan_mode
+                    .map(|mode| format!("{:?}", mode))
+                    .unwrap_or_else(|| "Unknown".to_string()),
+                state_guard
+                    .boot_performance_mode
+                    .map(|mode| mode.to_string())
+                    .unwrap_or_else(|| "Unknown".to_string())
+            );
+            let fields = [
+                ("Mode", state_guard.user_mode.to_string()),
+                ("Actual", format!("{:?}", state_guard.actual_mode)),
+                (
+                    "Performance",
+                    state_guard
+                        .performance_mode
+                        .map(|mode| mode.to_string())
+                        .unwrap_or_else(|| "Unknown".to_string()),
+                ),
+                ("Profiles", profiles_str),
+                ("Temp", temp_str),
+                ("Raw Temp", raw_temp_str),
+                ("Slope", slope_str),
+                (
+                    "Sensor",
+                    state_guard
+                        .temp_source
+                        .clone()
+                        .unwrap_or_else(|| "Unknown".to_string()),
+                ),
+                ("Fans", fans_str),
+                ("Boot", boot_str),
+                ("Resets", state_guard.fan_mode_resets.to_string()),
+                (
+                    "Board",
+                    state_guard
+                        .board_name
+                        .clone()
+                        .unwrap_or_else(|| "Unknown".to_string()),
+                ),
+                (
+                    "Quirk",
+                    state_guard
+                        .quirk
+                        .map(|quirk| quirk.description)
+                        .unwrap_or("None")
+                        .to_string(),
+                ),
+                ("Keyboard", keyboard_str),
+                ("Battery", battery_str),
+                ("Limit", limit_str),
+                (
+                    "Power",
+                    state_guard
+                        .hardware
+                        .read_power_source()
+                        .map(|source| source.to_string())
+                        .unwrap_or_else(|_| "Unknown".to_string()),
+                ),
+                (
+                    "CPU",
+                    state_guard
+                        .hardware
+                        .read_cpu_policy()
+                        .map(|policy| policy.to_string())
+                        .unwrap_or_else(|_| "Unknown".to_string()),
+                ),
+                ("Fan Source", state_guard.fan_mode_source.to_string()),
+                (
+                    "Performance Source",
+                    state_guard.performance_source.to_string(),
+                ),
+                ("Hold", hold_str),
+                (
+                    "Error",
+                    state_guard
+                        .sensor_error
+                        .clone()
+                        .unwrap_or_else(|| "None".to_string()),
+                ),
+                (
+                    "Alert",
+                    state_guard
+                        .critical_alert
+                        .clone()
+                        .unwrap_or_else(|| "None".to_string()),
+                ),
+            ];
+            Ok(format_status(&fields))
         }
         _ => Err(
-            "Invalid command. Use 'set <mode>', 'set_performance <mode>', or 'status'".to_string(),
+            "Invalid command. Use 'set <mode>', 'set manual <percent>', 'set_performance <mode>', 'get_kbd_backlight', 'set_kbd_backlight <brightness>', 'get_charge_limit', 'set_charge_limit <end|start-end>', or 'status'".to_string(),
         ),
     }
 }
 
-fn set_fan_mode(state: Arc<Mutex<DaemonState>>, new_mode: FanMode) -> Result<(), String> {
+/// Joins `Key: value` status fields with `, `, the separator is replaced
+/// inside values so clients can split the line apart again
+fn format_status(fields: &[(&str, String)]) -> String {
+    fields
+        .iter()
+        .map(|(key, value)| format!("{}: {}", key, value.replace(", ", "; ")))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn set_fan_mode(
+    state: Arc<Mutex<DaemonState>>,
+    new_mode: FanMode,
+) -> Result<FanWriteReport, String> {
     info!("Setting fan mode to: {:?}", new_mode);
 
-    let temp_threshold = {
+    let (config, hardware) = {
         let state_guard = state.lock().unwrap();
-        state_guard.config.temp_threshold_high * 1000
+        (state_guard.config.clone(), state_guard.hardware.clone())
     };
+    let temp_threshold = config.temp_threshold_high * 1000;
 
     let actual_mode_to_set = match new_mode {
         FanMode::Max => HardwareFanMode::Max,
         FanMode::Auto => {
             // For Auto mode, check current temperature
-            match read_temperature() {
-                Ok(temp) if temp > temp_threshold => HardwareFanMode::Max,
+            match hardware.read_temperature(&config.temp_sensors, config.temp_aggregation) {
+                Ok(reading) if reading.temp > temp_threshold => HardwareFanMode::Max,
                 _ => HardwareFanMode::Bios,
             }
         }
         FanMode::Bios => HardwareFanMode::Bios,
+        FanMode::Manual(percent) => {
+            // Reject before touching state so the current mode stays in effect
+            if !hardware.supports_manual_duty() {
+                return Err(
+                    "Manual fan mode is not supported on this board: no writable pwm duty attribute found"
+                        .to_string(),
+                );
+            }
+            HardwareFanMode::Manual(percent)
+        }
+        FanMode::Curve => {
+            let curve = FanCurve::new(&config.fan_curve).ok_or("No fan curve points configured")?;
+            match hardware.read_temperature(&config.temp_sensors, config.temp_aggregation) {
+                Ok(reading) if hardware.supports_manual_duty() => {
+                    HardwareFanMode::Manual(curve.duty_at(reading.temp / 1000))
+                }
+                // Without PWM duty control fall back to toggling at the curve midpoint
+                Ok(reading) if reading.temp > curve.midpoint() * 1000 => HardwareFanMode::Max,
+                _ => HardwareFanMode::Bios,
+            }
+        }
     };
 
     // Update state
@@ -221,7 +637,12 @@ fn set_fan_mode(state: Arc<Mutex<DaemonState>>, new_mode: FanMode) -> Result<(),
 
         state_guard.user_mode = new_mode;
         state_guard.actual_mode = actual_mode_to_set;
+        state_guard.fan_mode_source = ChangeSource::Daemon;
         state_guard.consecutive_high_temps = 0;
+        // The mode written here starts the minimum time for Auto mode
+        state_guard.auto_switched_at = Some(Instant::now());
+        state_guard.auto_hold_until = None;
+        state_guard.spin_down_since = None;
 
         match new_mode {
             FanMode::Max => {
@@ -239,6 +660,16 @@ fn set_fan_mode(state: Arc<Mutex<DaemonState>>, new_mode: FanMode) -> Result<(),
                 state_guard.last_fan_write = None;
                 info!("BIOS mode: Disabled temp monitoring and cleared last_fan_write");
             }
+            FanMode::Manual(_) => {
+                state_guard.temp_monitoring_active = false;
+                state_guard.last_fan_write = None;
+                info!("Manual mode: Disabled temp monitoring and cleared last_fan_write");
+            }
+            FanMode::Curve => {
+                state_guard.temp_monitoring_active = true;
+                state_guard.last_fan_write = None;
+                info!("Curve mode: Enabled temp monitoring and cleared last_fan_write");
+            }
         }
 
         let new_state = format!("{:?}", *state_guard);
@@ -248,11 +679,16 @@ fn set_fan_mode(state: Arc<Mutex<DaemonState>>, new_mode: FanMode) -> Result<(),
         );
     }
 
-    // Write to hardware
-    write_fan_mode(actual_mode_to_set).map_err(|e| format!("Failed to write fan mode: {}", e))?;
+    // Write to hardware, on failure track whatever the channels are left in
+    let report = write_fan_mode(&state, actual_mode_to_set).map_err(|e| {
+        if let Ok(mode) = hardware.read_fan_mode() {
+            state.lock().unwrap().actual_mode = mode;
+        }
+        format!("Failed to write fan mode: {}", e)
+    })?;
 
     info!("Successfully set fan mode to: {:?}", actual_mode_to_set);
-    Ok(())
+    Ok(report)
 }
 
 fn set_performance_mode(
@@ -262,47 +698,833 @@ fn set_performance_mode(
     info!("Setting performance mode to: {:?}", new_mode);
 
     // Update state
-    {
+    let hardware = {
         let mut state_guard = state.lock().unwrap();
-        state_guard.performance_mode = new_mode;
-    }
+        let choices = &state_guard.performance_choices;
+        if !choices.is_empty() && !choices.contains(&new_mode) {
+            let supported: Vec<String> = choices.iter().map(|mode| mode.to_string()).collect();
+            return Err(format!(
+                "Performance mode {} is not supported by this hardware (supported: {})",
+                new_mode,
+                supported.join(", ")
+            ));
+        }
+        state_guard.performance_mode = Some(new_mode);
+        state_guard.performance_source = ChangeSource::Daemon;
+        state_guard.hardware.clone()
+    };
 
     // Write to platform profile
-    write_performance_mode(new_mode)
+    hardware
+        .write_performance_mode(new_mode)
         .map_err(|e| format!("Failed to write performance mode: {}", e))?;
 
     info!("Successfully set performance mode to: {:?}", new_mode);
+    apply_cpu_policy(state, new_mode);
+    Ok(())
+}
+
+/// Applies the CPU policy mapped to a performance mode, modes without a
+/// mapping get the policy from startup back
+fn apply_cpu_policy(state: Arc<Mutex<DaemonState>>, mode: PerformanceMode) {
+    let (policy, hardware) = {
+        let state_guard = state.lock().unwrap();
+        let mapped = state_guard
+            .config
+            .cpu_policies
+            .iter()
+            .find(|mapping| mapping.performance_mode == mode)
+            .map(|mapping| mapping.policy.clone());
+        let policy = match mapped {
+            Some(policy) => policy,
+            None if state_guard.cpu_policy_applied => match &state_guard.cpu_policy_boot {
+                Some(policy) => policy.clone(),
+                None => return,
+            },
+            None => return,
+        };
+        (policy, state_guard.hardware.clone())
+    };
+
+    match hardware.write_cpu_policy(&policy) {
+        Ok(()) => info!("Applied CPU policy for {}: {}", mode, policy),
+        Err(e) => warn!("Failed to apply CPU policy for {}: {}", mode, e),
+    }
+    state.lock().unwrap().cpu_policy_applied = true;
+}
+
+/// Blocks the shutdown signals so only the shutdown handler thread receives
+/// them, must run before any other thread is spawned
+fn block_shutdown_signals() -> libc::sigset_t {
+    unsafe {
+        let mut signals: libc::sigset_t = std::mem::zeroed();
+        libc::sigemptyset(&mut signals);
+        libc::sigaddset(&mut signals, libc::SIGINT);
+        libc::sigaddset(&mut signals, libc::SIGTERM);
+        libc::sigaddset(&mut signals, libc::SIGHUP);
+        libc::pthread_sigmask(libc::SIG_BLOCK, &signals, std::ptr::null_mut());
+        signals
+    }
+}
+
+/// Waits for SIGINT/SIGTERM/SIGHUP and puts back what the daemon changed before exiting
+fn start_shutdown_handler(
+    state: Arc<Mutex<DaemonState>>,
+    restore: ExitRestore,
+    signals: libc::sigset_t,
+) {
+    thread::spawn(move || {
+        let mut signal = 0;
+        unsafe { libc::sigwait(&signals, &mut signal) };
+        info!("Received signal {}, shutting down", signal);
+
+        // The lock stays held until exit so no fan write lands after the restore
+        let mut state_guard = match state.lock() {
+            Ok(state_guard) => state_guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        state_guard.shutting_down = true;
+        restore.run(state_guard.critical_saved_cpu_freqs.clone());
+
+        std::process::exit(0);
+    });
+}
+
+/// Hands the fans back before the process dies on a panic in any thread,
+/// the state lock is only tried since the panicking thread may hold it
+fn install_panic_restore(state: Arc<Mutex<DaemonState>>, restore: ExitRestore) {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        error!("Daemon panicked, restoring hardware state before exiting");
+        let mut state_guard = match state.try_lock() {
+            Ok(state_guard) => Some(state_guard),
+            Err(TryLockError::Poisoned(poisoned)) => Some(poisoned.into_inner()),
+            Err(TryLockError::WouldBlock) => None,
+        };
+        let cpu_freqs = match state_guard.as_mut() {
+            Some(state_guard) => {
+                state_guard.shutting_down = true;
+                state_guard.critical_saved_cpu_freqs.clone()
+            }
+            None => None,
+        };
+        restore.run(cpu_freqs);
+        std::process::exit(101);
+    }));
+}
+
+/// Writes a fan mode with the state lock held, the shutdown handler keeps the
+/// lock once it restored the fans so nothing can overwrite them before exit
+fn write_fan_mode(
+    state: &Arc<Mutex<DaemonState>>,
+    mode: HardwareFanMode,
+) -> Result<FanWriteReport, io::Error> {
+    let state_guard = state.lock().unwrap();
+    if state_guard.shutting_down {
+        return Err(io::Error::other("Daemon is shutting down"));
+    }
+    state_guard.hardware.write_fan_mode(mode)
+}
+
+fn set_keyboard_backlight(
+    state: Arc<Mutex<DaemonState>>,
+    brightness: u32,
+) -> Result<KeyboardBacklight, String> {
+    info!("Setting keyboard backlight to: {}", brightness);
+
+    let hardware = state.lock().unwrap().hardware.clone();
+    let backlight = hardware
+        .read_keyboard_backlight()
+        .map_err(|e| format!("Failed to read keyboard backlight: {}", e))?;
+    if brightness > backlight.max_brightness {
+        return Err(format!(
+            "Keyboard backlight brightness {} out of range (0-{})",
+            brightness, backlight.max_brightness
+        ));
+    }
+
+    hardware
+        .write_keyboard_backlight(brightness)
+        .map_err(|e| format!("Failed to write keyboard backlight: {}", e))?;
+
+    // An explicit change replaces whatever the idle auto-off would restore
+    state.lock().unwrap().kbd_backlight_saved = None;
+
+    info!("Successfully set keyboard backlight to: {}", brightness);
+    Ok(KeyboardBacklight {
+        brightness,
+        ..backlight
+    })
+}
+
+fn set_charge_limit(state: Arc<Mutex<DaemonState>>, limit: ChargeLimit) -> Result<(), String> {
+    info!("Setting charge limit to: {}", limit);
+
+    let (hardware, state_dir) = {
+        let state_guard = state.lock().unwrap();
+        (
+            state_guard.hardware.clone(),
+            state_guard.config.state_dir.clone(),
+        )
+    };
+
+    hardware
+        .write_charge_limit(limit)
+        .map_err(|e| format!("Failed to write charge limit: {}", e))?;
+
+    // The kernel resets the thresholds on boot, keep them for the next start
+    if let Err(e) = StateStore::new(&state_dir).save(CHARGE_LIMIT_KEY, &limit) {
+        warn!("Failed to save charge limit: {}", e);
+    }
+
+    info!("Successfully set charge limit to: {}", limit);
     Ok(())
 }
 
+/// Saves a value chosen by the user so the next start picks it up again
+fn save_user_setting<T: std::fmt::Display>(state: &Arc<Mutex<DaemonState>>, key: &str, value: &T) {
+    let state_dir = state.lock().unwrap().config.state_dir.clone();
+    if let Err(e) = StateStore::new(&state_dir).save(key, value) {
+        warn!("Failed to save {}: {}", key, e);
+    }
+}
+
+/// Applies the configured profile for a power source, user changes stay in
+/// effect until the next transition
+fn apply_power_profile(state: Arc<Mutex<DaemonState>>, source: PowerSource) {
+    let profile = {
+        let state_guard = state.lock().unwrap();
+        match source {
+            PowerSource::Ac => state_guard.config.ac_profile,
+            PowerSource::Battery => state_guard.config.battery_profile,
+        }
+    };
+    let Some(profile) = profile else {
+        debug!("No power profile configured for {}", source);
+        return;
+    };
+
+    info!("Applying {} power profile: {}", source, profile);
+    if let Err(e) = set_fan_mode(state.clone(), profile.fan_mode) {
+        error!("Failed to apply {} fan mode: {}", source, e);
+    }
+    if let Err(e) = set_performance_mode(state, profile.performance_mode) {
+        error!("Failed to apply {} performance mode: {}", source, e);
+    }
+}
+
+/// Watches the power supplies and applies the AC or battery profile on every
+/// transition, the first reading only counts as one when `apply_initial` is set
+fn start_power_source_monitor(state: Arc<Mutex<DaemonState>>, apply_initial: bool) {
+    info!("Starting power source monitoring thread");
+    thread::spawn(move || {
+        let hardware = state.lock().unwrap().hardware.clone();
+        loop {
+            match hardware.read_power_source() {
+                Ok(source) => {
+                    let previous = state.lock().unwrap().power_source.replace(source);
+                    match previous {
+                        None if !apply_initial => {
+                            info!(
+                                "Power source at startup: {}, keeping the startup modes",
+                                source
+                            );
+                        }
+                        Some(previous) if previous == source => {}
+                        _ => {
+                            info!(
+                                "Power source changed: {} -> {}",
+                                previous
+                                    .map(|source| source.to_string())
+                                    .unwrap_or_else(|| "Unknown".to_string()),
+                                source
+                            );
+                            apply_power_profile(state.clone(), source);
+                        }
+                    }
+                }
+                Err(e) => {
+                    error!(
+                        "Failed to read power source, stopping power source monitor: {}",
+                        e
+                    );
+                    return;
+                }
+            }
+            thread::sleep(POWER_SOURCE_CHECK_INTERVAL);
+        }
+    });
+}
+
+/// Turns the keyboard backlight off after `timeout` without keyboard input
+/// and restores it on the next key press
+fn start_keyboard_backlight_monitor(state: Arc<Mutex<DaemonState>>, timeout: Duration) {
+    info!(
+        "Starting keyboard backlight idle monitor (timeout: {:?})",
+        timeout
+    );
+    thread::spawn(move || {
+        let hardware = state.lock().unwrap().hardware.clone();
+        let mut last_activity_count = None;
+        let mut last_activity = Instant::now();
+        loop {
+            thread::sleep(Duration::from_secs(1));
+
+            let activity_count = match hardware.read_keyboard_activity() {
+                Ok(count) => count,
+                Err(e) => {
+                    error!(
+                        "Failed to read keyboard activity, stopping idle monitor: {}",
+                        e
+                    );
+                    return;
+                }
+            };
+            let active = last_activity_count.is_some_and(|last| last != activity_count);
+            last_activity_count = Some(activity_count);
+
+            if active {
+                last_activity = Instant::now();
+                let saved = state.lock().unwrap().kbd_backlight_saved.take();
+                if let Some(brightness) = saved {
+                    debug!("Keyboard used, restoring backlight to {}", brightness);
+                    if let Err(e) = hardware.write_keyboard_backlight(brightness) {
+                        error!("Failed to restore keyboard backlight: {}", e);
+                    }
+                }
+                continue;
+            }
+
+            if last_activity.elapsed() < timeout
+                || state.lock().unwrap().kbd_backlight_saved.is_some()
+            {
+                continue;
+            }
+
+            match hardware.read_keyboard_backlight() {
+                Ok(backlight) if backlight.brightness > 0 => {
+                    debug!(
+                        "Keyboard idle for {:?}, turning backlight off",
+                        last_activity.elapsed()
+                    );
+                    if let Err(e) = hardware.write_keyboard_backlight(0) {
+                        error!("Failed to turn keyboard backlight off: {}", e);
+                    } else {
+                        state.lock().unwrap().kbd_backlight_saved = Some(backlight.brightness);
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => debug!("Failed to read keyboard backlight: {}", e),
+            }
+        }
+    });
+}
+
+/// Polls the fan control and platform profile attributes (sysfs does not
+/// support inotify) and reconciles changes the daemon did not make.
+/// A change has to show up on two polls in a row so in-flight daemon writes are not mistaken for one
+fn start_external_change_watcher(state: Arc<Mutex<DaemonState>>) {
+    info!("Starting external change watcher thread");
+    thread::spawn(move || {
+        let hardware = state.lock().unwrap().hardware.clone();
+        let mut pending_fan_mode = None;
+        let mut pending_performance_mode = None;
+        loop {
+            thread::sleep(EXTERNAL_CHANGE_CHECK_INTERVAL);
+
+            let expected_mode = state.lock().unwrap().actual_mode;
+            match hardware.read_fan_mode() {
+                Ok(mode) if !fan_modes_match(expected_mode, mode) => {
+                    if pending_fan_mode.replace(mode) == Some(mode) {
+                        pending_fan_mode = None;
+                        handle_external_fan_mode(&state, expected_mode, mode);
+                    }
+                }
+                Ok(_) => pending_fan_mode = None,
+                Err(e) => debug!("Failed to read back fan mode: {}", e),
+            }
+
+            let expected_performance_mode = state.lock().unwrap().performance_mode;
+            match hardware.read_performance_mode() {
+                Ok(mode) if Some(mode) != expected_performance_mode => {
+                    if pending_performance_mode.replace(mode) == Some(mode) {
+                        pending_performance_mode = None;
+                        info!(
+                            "Platform profile changed externally: {} -> {}",
+                            expected_performance_mode
+                                .map(|mode| mode.to_string())
+                                .unwrap_or_else(|| "unknown".to_string()),
+                            mode
+                        );
+                        {
+                            let mut state_guard = state.lock().unwrap();
+                            state_guard.performance_mode = Some(mode);
+                            state_guard.performance_source = ChangeSource::External;
+                        }
+                        apply_cpu_policy(state.clone(), mode);
+                    }
+                }
+                Ok(_) => pending_performance_mode = None,
+                Err(e) => debug!("Failed to read back platform profile: {}", e),
+            }
+        }
+    });
+}
+
+/// A fall back to BIOS control is the firmware resetting our mode and gets
+/// re-asserted, any other change was made on purpose and is adopted
+fn handle_external_fan_mode(
+    state: &Arc<Mutex<DaemonState>>,
+    expected_mode: HardwareFanMode,
+    mode: HardwareFanMode,
+) {
+    let mut state_guard = state.lock().unwrap();
+
+    if mode == HardwareFanMode::Bios {
+        state_guard.fan_mode_resets += 1;
+        warn!(
+            "Fan mode reset detected: expected {:?}, hardware reports {:?} (reset #{}), re-asserting",
+            expected_mode, mode, state_guard.fan_mode_resets
+        );
+        drop(state_guard);
+        if let Err(e) = write_fan_mode(state, expected_mode) {
+            error!("Failed to re-assert fan mode: {}", e);
+        } else if expected_mode == HardwareFanMode::Max {
+            let mut state_guard = state.lock().unwrap();
+            state_guard.last_fan_write = Some(Instant::now());
+        }
+        return;
+    }
+
+    info!(
+        "Fan mode changed externally: {:?} -> {:?}",
+        expected_mode, mode
+    );
+    state_guard.user_mode = match mode {
+        HardwareFanMode::Max => FanMode::Max,
+        HardwareFanMode::Bios => FanMode::Bios,
+        HardwareFanMode::Manual(percent) => FanMode::Manual(percent),
+    };
+    state_guard.actual_mode = mode;
+    state_guard.fan_mode_source = ChangeSource::External;
+    state_guard.temp_monitoring_active = false;
+    state_guard.last_fan_write = (mode == HardwareFanMode::Max).then(Instant::now);
+}
+
+/// Manual duty is compared with 1% tolerance since the pwm value loses precision
+fn fan_modes_match(expected: HardwareFanMode, actual: HardwareFanMode) -> bool {
+    match (expected, actual) {
+        (HardwareFanMode::Manual(expected), HardwareFanMode::Manual(actual)) => {
+            expected.abs_diff(actual) <= 1
+        }
+        _ => expected == actual,
+    }
+}
+
+/// Counts a failed or implausible temperature reading, after
+/// `sensor_failure_limit` in a row the fans are forced to max while Auto or
+/// Curve mode would otherwise act on stale data
+fn handle_sensor_failure(state: &Arc<Mutex<DaemonState>>, error: &str) {
+    {
+        let mut state_guard = state.lock().unwrap();
+        state_guard.sensor_failures += 1;
+        state_guard.current_temp = None;
+        state_guard.raw_temp = None;
+        state_guard.temp_slope = None;
+        if state_guard.sensor_failures < state_guard.config.sensor_failure_limit {
+            return;
+        }
+
+        if state_guard.sensor_error.is_none() {
+            error!(
+                "Temperature sensor failed {} times in a row, engaging failsafe",
+                state_guard.sensor_failures
+            );
+        }
+        state_guard.sensor_error = Some(format!("Temperature sensor failed: {}", error));
+
+        let rewrite_due = match (
+            state_guard.config.max_fan_write_interval,
+            state_guard.last_fan_write,
+        ) {
+            (Some(interval), Some(last_write)) => {
+                last_write.elapsed() >= Duration::from_secs(interval)
+            }
+            _ => false,
+        };
+        let needs_max = state_guard.actual_mode != HardwareFanMode::Max || rewrite_due;
+        if !state_guard.temp_monitoring_active || !needs_max {
+            return;
+        }
+    }
+
+    match write_fan_mode(state, HardwareFanMode::Max) {
+        Ok(_) => {
+            warn!("Failsafe: fans forced to max until temperature readings return");
+            let mut state_guard = state.lock().unwrap();
+            state_guard.actual_mode = HardwareFanMode::Max;
+            state_guard.last_fan_write = Some(Instant::now());
+        }
+        Err(e) => error!("Failsafe failed to set max fan mode: {}", e),
+    }
+}
+
+/// Clears the failure count and failsafe once a plausible reading comes in,
+/// the fan mode is left to the monitor to bring back down
+fn recover_from_sensor_failure(state: &Arc<Mutex<DaemonState>>) {
+    let mut state_guard = state.lock().unwrap();
+    state_guard.sensor_failures = 0;
+    if state_guard.sensor_error.take().is_some() {
+        info!("Temperature readings recovered, failsafe cleared");
+    }
+}
+
+/// Escalates while the temperature stays above `critical` with the fans at
+/// max and undoes the escalation once it drops `CRITICAL_RECOVERY_MARGIN` below
+fn handle_critical_temperature(state: &Arc<Mutex<DaemonState>>, temp: i32, critical: i32) {
+    let now = Instant::now();
+    let mut state_guard = state.lock().unwrap();
+    let fans_at_max = matches!(
+        state_guard.actual_mode,
+        HardwareFanMode::Max | HardwareFanMode::Manual(100)
+    );
+
+    if temp > critical * 1000 && fans_at_max {
+        let since = *state_guard.critical_since.get_or_insert(now);
+        let elapsed = now.duration_since(since);
+        let step = Duration::from_secs(state_guard.config.temp_critical_duration);
+        let Some(stage) = CriticalStage::reached(elapsed, step) else {
+            return;
+        };
+        state_guard.critical_alert = Some(format!(
+            "Critical temperature {}°C for {} s ({})",
+            temp / 1000,
+            elapsed.as_secs(),
+            stage
+        ));
+
+        // Take every step up to the one reached, each only once
+        let done = state_guard.critical_stage;
+        let pending: Vec<CriticalStage> = CriticalStage::ALL
+            .into_iter()
+            .filter(|pending| *pending <= stage && done.is_none_or(|done| *pending > done))
+            .collect();
+        if pending.is_empty() {
+            return;
+        }
+        state_guard.critical_stage = Some(stage);
+        drop(state_guard);
+        for stage in pending {
+            escalate_critical(state, stage, temp);
+        }
+        return;
+    }
+
+    // The timer restarts whenever the condition breaks, steps taken stay in effect
+    state_guard.critical_since = None;
+    if temp >= (critical - CRITICAL_RECOVERY_MARGIN) * 1000 || state_guard.critical_stage.is_none()
+    {
+        return;
+    }
+
+    state_guard.critical_stage = None;
+    state_guard.critical_alert = None;
+    let saved_performance = state_guard.critical_saved_performance.take();
+    let saved_cpu_freqs = state_guard.critical_saved_cpu_freqs.take();
+    let current_performance = state_guard.performance_mode;
+    let hardware = state_guard.hardware.clone();
+    drop(state_guard);
+
+    info!(
+        "Temperature back to {}°C, undoing critical temperature actions",
+        temp / 1000
+    );
+    match saved_performance {
+        // Leave the profile alone if it was changed since
+        Some((previous, applied)) if Some(applied) == current_performance => {
+            match set_performance_mode(state.clone(), previous) {
+                Ok(()) => info!("Restored performance mode {}", previous),
+                Err(e) => error!("Failed to restore performance mode {}: {}", previous, e),
+            }
+        }
+        _ => {}
+    }
+    if let Some(freqs) = saved_cpu_freqs {
+        match hardware.write_cpu_max_freqs(&freqs) {
+            Ok(()) => info!("Restored CPU frequency limits"),
+            Err(e) => error!("Failed to restore CPU frequency limits: {}", e),
+        }
+    }
+}
+
+fn escalate_critical(state: &Arc<Mutex<DaemonState>>, stage: CriticalStage, temp: i32) {
+    let (config, hardware, current_performance, choices) = {
+        let state_guard = state.lock().unwrap();
+        (
+            state_guard.config.clone(),
+            state_guard.hardware.clone(),
+            state_guard.performance_mode,
+            state_guard.performance_choices.clone(),
+        )
+    };
+    error!(
+        "Critical temperature {}°C with fans at max, escalating: {}",
+        temp / 1000,
+        stage
+    );
+
+    match stage {
+        CriticalStage::PowerSaver => {
+            // Lowest power profile the firmware offers
+            let target = [
+                PerformanceMode::PowerSaver,
+                PerformanceMode::LowPower,
+                PerformanceMode::Quiet,
+                PerformanceMode::Cool,
+            ]
+            .into_iter()
+            .find(|mode| choices.is_empty() || choices.contains(mode))
+            .unwrap_or(PerformanceMode::PowerSaver);
+            if current_performance == Some(target) {
+                info!("Performance mode already {}", target);
+                return;
+            }
+            match set_performance_mode(state.clone(), target) {
+                Ok(()) => {
+                    warn!("Performance mode dropped to {}", target);
+                    // An unknown profile before the drop cannot be restored
+                    state.lock().unwrap().critical_saved_performance =
+                        current_performance.map(|previous| (previous, target));
+                }
+                Err(e) => error!("Failed to drop performance mode: {}", e),
+            }
+        }
+        CriticalStage::CpuCap => {
+            let Some(cap_mhz) = config.critical_cpu_max_freq else {
+                info!("No critical CPU frequency cap configured");
+                return;
+            };
+            let freqs = match hardware.read_cpu_max_freqs() {
+                Ok(freqs) => freqs,
+                Err(e) => {
+                    error!("Failed to read CPU frequency limits: {}", e);
+                    return;
+                }
+            };
+            let capped: Vec<u32> = freqs
+                .iter()
+                .map(|freq| (*freq).min(cap_mhz * 1000))
+                .collect();
+            match hardware.write_cpu_max_freqs(&capped) {
+                Ok(()) => {
+                    warn!("CPU frequency capped to {} MHz", cap_mhz);
+                    state.lock().unwrap().critical_saved_cpu_freqs = Some(freqs);
+                }
+                Err(e) => error!("Failed to cap CPU frequency: {}", e),
+            }
+        }
+        CriticalStage::Command => {
+            let Some(command) = config.critical_command else {
+                info!("No critical command configured");
+                return;
+            };
+            warn!("Running critical command: {}", command);
+            match std::process::Command::new("sh")
+                .arg("-c")
+                .arg(&command)
+                .spawn()
+            {
+                Ok(mut child) => {
+                    // Reap the child without holding up the monitor
+                    thread::spawn(move || match child.wait() {
+                        Ok(status) => info!("Critical command exited with {}", status),
+                        Err(e) => error!("Failed to wait for critical command: {}", e),
+                    });
+                }
+                Err(e) => error!("Failed to run critical command {}: {}", command, e),
+            }
+        }
+    }
+}
+
+/// Returns when Auto mode may switch to `target` if the minimum time in the
+/// current mode or the transition rate limit says it has to wait
+fn auto_switch_hold(
+    state: &mut DaemonState,
+    target: HardwareFanMode,
+    now: Instant,
+) -> Option<Instant> {
+    let window = Duration::from_secs(state.config.auto_transition_window);
+    while state
+        .auto_switches
+        .front()
+        .is_some_and(|switched_at| now.duration_since(*switched_at) >= window)
+    {
+        state.auto_switches.pop_front();
+    }
+
+    let min_time = match target {
+        HardwareFanMode::Max => state.config.auto_min_bios_time,
+        _ => state.config.auto_min_max_time,
+    };
+    let dwell_until = state
+        .auto_switched_at
+        .map(|switched_at| switched_at + Duration::from_secs(min_time))
+        .filter(|until| *until > now);
+
+    // Out of transitions the fans stay on max, never the other way around
+    let rate_until = match (target, state.config.auto_max_transitions) {
+        (HardwareFanMode::Bios, Some(limit)) if state.auto_switches.len() >= limit as usize => {
+            state.auto_switches.front().map(|oldest| *oldest + window)
+        }
+        _ => None,
+    };
+    dwell_until.max(rate_until)
+}
+
+/// Returns when Auto mode may hand the fans back to BIOS control once the
+/// temperature is consistently low, the later of the spin-down delay and
+/// `auto_switch_hold`
+fn auto_spin_down_hold(state: &mut DaemonState, now: Instant) -> Option<Instant> {
+    // The spin-down delay starts once the low readings are consistent
+    let spin_down_since = *state.spin_down_since.get_or_insert(now);
+    let spin_down_until = spin_down_since + Duration::from_secs(state.config.auto_spin_down_delay);
+    auto_switch_hold(state, HardwareFanMode::Bios, now)
+        .max((spin_down_until > now).then_some(spin_down_until))
+}
+
+/// Records a BIOS/Max switch made by Auto mode
+fn record_auto_switch(state: &mut DaemonState, mode: HardwareFanMode, now: Instant) {
+    state.actual_mode = mode;
+    // Track Max writes for the rewrite interval, cleared when not in max mode
+    state.last_fan_write = (mode == HardwareFanMode::Max).then_some(now);
+    state.consecutive_high_temps = 0;
+    state.consecutive_low_temps = 0;
+    state.auto_switched_at = Some(now);
+    state.auto_switches.push_back(now);
+    state.auto_hold_until = None;
+    state.spin_down_since = None;
+}
+
 fn start_temperature_monitor(state: Arc<Mutex<DaemonState>>) {
     info!("Starting temperature monitoring thread");
     thread::spawn(move || {
-        let config = {
+        let (config, hardware) = {
             let state_guard = state.lock().unwrap();
-            state_guard.config.clone()
+            (state_guard.config.clone(), state_guard.hardware.clone())
         };
+        let fan_curve = FanCurve::new(&config.fan_curve);
+        let mut pid = match config.pid_setpoint {
+            Some(setpoint) if hardware.supports_manual_duty() => {
+                info!("Auto mode uses PID control towards {}°C", setpoint);
+                Some(PidController::new(
+                    setpoint,
+                    config.pid_kp,
+                    config.pid_ki,
+                    config.pid_kd,
+                    config.pid_min_duty,
+                    config.pid_max_duty,
+                ))
+            }
+            Some(_) => {
+                warn!("PID control needs a writable pwm duty attribute, Auto mode uses thresholds");
+                None
+            }
+            None => None,
+        };
+        // None until the controller has taken over from the current fan mode
+        let mut last_pid_update: Option<Instant> = None;
+        let mut trend = TempTrend::new(Duration::from_secs(config.temp_slope_window));
+        let mut smoother = TempSmoother::new(
+            config.temp_filter,
+            config.temp_filter_alpha,
+            config.temp_filter_window,
+        );
         info!("Temperature monitoring thread started");
         loop {
             thread::sleep(Duration::from_secs(config.temp_check_interval));
 
-            // Read current temperature
-            let current_temp = match read_temperature() {
-                Ok(temp) => {
+            // Sample fan tachometers so status reflects what the fans are actually doing
+            match hardware.read_fan_speeds() {
+                Ok(speeds) => {
+                    let mut state_guard = state.lock().unwrap();
+                    state_guard.fan_speeds = speeds;
+                }
+                Err(e) => {
+                    debug!("Failed to read fan speeds: {}", e);
+                    let mut state_guard = state.lock().unwrap();
+                    state_guard.fan_speeds.clear();
+                }
+            }
+
+            // Read current temperature, readings outside the plausible range count as failures
+            let reading = hardware
+                .read_temperature(&config.temp_sensors, config.temp_aggregation)
+                .and_then(|reading| {
+                    let range = config.temp_plausible_min..=config.temp_plausible_max;
+                    if range.contains(&(reading.temp / 1000)) {
+                        Ok(reading)
+                    } else {
+                        Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!(
+                                "implausible reading {}°C from {}",
+                                reading.temp / 1000,
+                                reading.source
+                            ),
+                        ))
+                    }
+                });
+            let current_temp = match reading {
+                Ok(reading) => {
+                    recover_from_sensor_failure(&state);
+                    let filtered = smoother.apply(reading.temp);
+                    if filtered != reading.temp {
+                        debug!(
+                            "Filtered temperature: {:.1}°C (raw {:.1}°C)",
+                            f64::from(filtered) / 1000.0,
+                            f64::from(reading.temp) / 1000.0
+                        );
+                    }
+                    trend.push(Instant::now(), filtered);
                     {
                         let mut state_guard = state.lock().unwrap();
-                        state_guard.current_temp = Some(temp);
+                        state_guard.current_temp = Some(filtered);
+                        state_guard.raw_temp = Some(reading.temp);
+                        state_guard.temp_source = Some(reading.source);
+                        state_guard.temp_slope = trend.slope();
                     }
-                    temp
+                    filtered
                 }
                 Err(e) => {
                     error!("Failed to read temperature: {}", e);
+                    handle_sensor_failure(&state, &e.to_string());
+                    // The controller starts over from max fans once readings return
+                    last_pid_update = None;
                     continue;
                 }
             };
 
+            if let Some(critical) = config.temp_critical {
+                handle_critical_temperature(&state, current_temp, critical);
+            }
+
+            let slope = trend.slope();
+            if let Some(slope) = slope {
+                debug!("Temperature slope: {:+.2}°C/s", slope);
+            }
+            let rising_fast = match (slope, config.temp_slope_limit) {
+                (Some(slope), Some(limit)) => slope > limit,
+                _ => false,
+            };
+
             let mut should_handle_max_mode = false;
             let mut should_handle_auto_mode = false;
+            let mut should_handle_pid = false;
+            let mut curve_to_apply = None;
+            let mut high_threshold = config.temp_threshold_high;
+            let mut low_threshold = config.temp_threshold_low;
             let user_mode;
 
             // Check what we need to do
@@ -311,29 +1533,45 @@ fn start_temperature_monitor(state: Arc<Mutex<DaemonState>>) {
                 user_mode = state_guard.user_mode;
 
                 if user_mode == FanMode::Max {
-                    if let Some(interval) = config.max_fan_write_interval {
-                        if let Some(last_write) = state_guard.last_fan_write {
+                    match (config.max_fan_write_interval, state_guard.last_fan_write) {
+                        (Some(interval), Some(last_write)) => {
                             let max_fan_write_interval = Duration::from_secs(interval);
-                            let elapsed = last_write.elapsed();
-                            if elapsed >= max_fan_write_interval {
-                                should_handle_max_mode = true;
-                            }
-                        } else {
-                            should_handle_max_mode = true;
+                            should_handle_max_mode = last_write.elapsed() >= max_fan_write_interval;
                         }
+                        (Some(_), None) => should_handle_max_mode = true,
+                        // If max_fan_write_interval is None, don't rewrite max mode
+                        (None, _) => {}
                     }
-                    // If max_fan_write_interval is None, don't rewrite max mode
                 }
 
                 if user_mode == FanMode::Auto && state_guard.temp_monitoring_active {
-                    should_handle_auto_mode = true;
+                    match pid {
+                        Some(_) => should_handle_pid = true,
+                        None => should_handle_auto_mode = true,
+                    }
+                }
+
+                if user_mode == FanMode::Curve && state_guard.temp_monitoring_active {
+                    match &fan_curve {
+                        Some(curve) if hardware.supports_manual_duty() => {
+                            curve_to_apply = Some(curve);
+                        }
+                        Some(curve) => {
+                            // No PWM duty control - use the Auto toggle around the curve midpoint
+                            should_handle_auto_mode = true;
+                            high_threshold = curve.midpoint();
+                            low_threshold = high_threshold
+                                - (config.temp_threshold_high - config.temp_threshold_low);
+                        }
+                        None => {}
+                    }
                 }
             }
 
             // Handle max mode timing - CRITICAL: Must rewrite every 100 seconds
             if should_handle_max_mode {
                 info!("Handling max mode timing - rewriting to maintain max fans");
-                if let Err(e) = write_fan_mode(HardwareFanMode::Max) {
+                if let Err(e) = write_fan_mode(&state, HardwareFanMode::Max) {
                     error!("Failed to set max fan mode: {}", e);
                 } else {
                     let mut state_guard = state.lock().unwrap();
@@ -343,45 +1581,143 @@ fn start_temperature_monitor(state: Arc<Mutex<DaemonState>>) {
                 }
             }
 
+            // Handle curve mode by writing the interpolated duty when it changes
+            if let Some(curve) = curve_to_apply {
+                let duty = curve.duty_at(current_temp / 1000);
+                let actual_mode = state.lock().unwrap().actual_mode;
+                if actual_mode != HardwareFanMode::Manual(duty) {
+                    debug!(
+                        "Curve mode: {}°C -> {}% duty (was {:?})",
+                        current_temp / 1000,
+                        duty,
+                        actual_mode
+                    );
+                    if let Err(e) = write_fan_mode(&state, HardwareFanMode::Manual(duty)) {
+                        error!("Failed to write fan curve duty: {}", e);
+                    } else {
+                        let mut state_guard = state.lock().unwrap();
+                        state_guard.actual_mode = HardwareFanMode::Manual(duty);
+                    }
+                }
+            }
+
+            // Handle auto mode with the PID controller on boards with duty control
+            match pid.as_mut() {
+                Some(pid) if should_handle_pid => {
+                    let now = Instant::now();
+                    let actual_mode = state.lock().unwrap().actual_mode;
+                    let dt = match last_pid_update {
+                        Some(last_update) => now.duration_since(last_update).as_secs_f64(),
+                        None => {
+                            // Start from the current duty, Max and BIOS count as the extremes
+                            pid.reset(match actual_mode {
+                                HardwareFanMode::Manual(duty) => duty,
+                                HardwareFanMode::Max => 100,
+                                HardwareFanMode::Bios => 0,
+                            });
+                            0.0
+                        }
+                    };
+                    last_pid_update = Some(now);
+
+                    let duty = if rising_fast {
+                        // Ramp up ahead of the controller, it carries on from the top
+                        let duty = pid.max_duty();
+                        pid.reset(duty);
+                        if actual_mode != HardwareFanMode::Manual(duty) {
+                            info!(
+                                "Temperature rising at {:+.1}°C/s at {}°C, PID jumping to {}% duty early",
+                                slope.unwrap_or_default(),
+                                current_temp / 1000,
+                                duty
+                            );
+                        }
+                        duty
+                    } else {
+                        pid.update(f64::from(current_temp) / 1000.0, dt)
+                    };
+                    if actual_mode != HardwareFanMode::Manual(duty) {
+                        debug!(
+                            "PID mode: {}°C -> {}% duty (was {:?})",
+                            current_temp / 1000,
+                            duty,
+                            actual_mode
+                        );
+                        if let Err(e) = write_fan_mode(&state, HardwareFanMode::Manual(duty)) {
+                            error!("Failed to write PID fan duty: {}", e);
+                        } else {
+                            let mut state_guard = state.lock().unwrap();
+                            state_guard.actual_mode = HardwareFanMode::Manual(duty);
+                        }
+                    }
+                }
+                _ => last_pid_update = None,
+            }
+
             // Handle auto mode temperature monitoring
             if should_handle_auto_mode {
                 debug!("Handling auto mode temperature check");
                 let temp_celsius = current_temp / 1000;
                 debug!(
                     "Temperature check: {}°C (high_threshold: {}°C, low_threshold: {}°C)",
-                    temp_celsius, config.temp_threshold_high, config.temp_threshold_low
+                    temp_celsius, high_threshold, low_threshold
                 );
 
+                let now = Instant::now();
                 let mut state_guard = state.lock().unwrap();
+                // Cleared every check so a hold ends once the switch is no longer wanted
+                let was_holding = state_guard.auto_hold_until.take().is_some();
 
                 if state_guard.actual_mode != HardwareFanMode::Max {
                     // Not in MAX mode - check for going to MAX
-                    if current_temp > config.temp_threshold_high * 1000 {
+                    let switch_reason = if rising_fast {
+                        Some(format!(
+                            "Temperature rising at {:+.1}°C/s at {}°C, switching to max fans early",
+                            slope.unwrap_or_default(),
+                            temp_celsius
+                        ))
+                    } else if current_temp > high_threshold * 1000 {
                         state_guard.consecutive_high_temps += 1;
                         info!(
                             "High temperature detected: {}°C (high_count: {})",
                             temp_celsius, state_guard.consecutive_high_temps
                         );
-
-                        if state_guard.consecutive_high_temps >= config.consecutive_high_temp_limit
-                        {
-                            info!("Temperature consistently high, switching to max fans");
-                            drop(state_guard);
-                            if let Err(e) = write_fan_mode(HardwareFanMode::Max) {
-                                error!("Failed to set max fan mode: {}", e);
-                            } else {
-                                let mut state_guard = state.lock().unwrap();
-                                state_guard.actual_mode = HardwareFanMode::Max;
-                                state_guard.last_fan_write = Some(Instant::now()); // Track for 100s rule
-                                state_guard.consecutive_high_temps = 0; // Reset counter after switching
-                            }
-                        }
+                        (state_guard.consecutive_high_temps >= config.consecutive_high_temp_limit)
+                            .then(|| {
+                                "Temperature consistently high, switching to max fans".to_string()
+                            })
                     } else {
                         // Temperature not high enough - reset high counter
                         if state_guard.consecutive_high_temps > 0 {
                             state_guard.consecutive_high_temps = 0;
                             debug!("Temperature normal, resetting high temperature counter");
                         }
+                        None
+                    };
+
+                    if let Some(reason) = switch_reason {
+                        match auto_switch_hold(&mut state_guard, HardwareFanMode::Max, now) {
+                            Some(until) => {
+                                state_guard.auto_hold_until = Some(until);
+                                if !was_holding {
+                                    info!(
+                                        "Holding {:?} for {} more s before switching to max fans",
+                                        state_guard.actual_mode,
+                                        until.duration_since(now).as_secs().max(1)
+                                    );
+                                }
+                            }
+                            None => {
+                                info!("{}", reason);
+                                drop(state_guard);
+                                if let Err(e) = write_fan_mode(&state, HardwareFanMode::Max) {
+                                    error!("Failed to set max fan mode: {}", e);
+                                } else {
+                                    let mut state_guard = state.lock().unwrap();
+                                    record_auto_switch(&mut state_guard, HardwareFanMode::Max, now);
+                                }
+                            }
+                        }
                     }
                 } else {
                     // In MAX mode - first maintain write interval rule if configured, then check for going back to BIOS
@@ -399,7 +1735,7 @@ fn start_temperature_monitor(state: Arc<Mutex<DaemonState>>) {
                     if should_rewrite_max {
                         drop(state_guard);
                         info!("Auto mode: Rewriting max fans to maintain 100s rule");
-                        if let Err(e) = write_fan_mode(HardwareFanMode::Max) {
+                        if let Err(e) = write_fan_mode(&state, HardwareFanMode::Max) {
                             error!("Failed to maintain max fan mode: {}", e);
                         } else {
                             let mut state_guard = state.lock().unwrap();
@@ -408,8 +1744,8 @@ fn start_temperature_monitor(state: Arc<Mutex<DaemonState>>) {
                         continue; // Skip the rest of this iteration
                     }
 
-                    // Now check for low temperature
-                    if current_temp <= config.temp_threshold_low * 1000 {
+                    // Now check for low temperature, a fast rise keeps the fans on max
+                    if current_temp <= low_threshold * 1000 && !rising_fast {
                         state_guard.consecutive_low_temps += 1;
                         info!(
                             "Low temperature detected: {}°C (low_count: {})",
@@ -417,15 +1753,32 @@ fn start_temperature_monitor(state: Arc<Mutex<DaemonState>>) {
                         );
 
                         if state_guard.consecutive_low_temps >= config.consecutive_low_temp_limit {
-                            info!("Temperature consistently low, switching back to BIOS control");
-                            drop(state_guard);
-                            if let Err(e) = write_fan_mode(HardwareFanMode::Bios) {
-                                error!("Failed to set BIOS fan mode: {}", e);
-                            } else {
-                                let mut state_guard = state.lock().unwrap();
-                                state_guard.actual_mode = HardwareFanMode::Bios;
-                                state_guard.last_fan_write = None; // Clear since not in max mode
-                                state_guard.consecutive_low_temps = 0; // Reset counter after switching
+                            match auto_spin_down_hold(&mut state_guard, now) {
+                                Some(until) => {
+                                    state_guard.auto_hold_until = Some(until);
+                                    if !was_holding {
+                                        info!(
+                                            "Holding Max for {} more s before switching back to BIOS control",
+                                            until.duration_since(now).as_secs().max(1)
+                                        );
+                                    }
+                                }
+                                None => {
+                                    info!(
+                                        "Temperature consistently low, switching back to BIOS control"
+                                    );
+                                    drop(state_guard);
+                                    if let Err(e) = write_fan_mode(&state, HardwareFanMode::Bios) {
+                                        error!("Failed to set BIOS fan mode: {}", e);
+                                    } else {
+                                        let mut state_guard = state.lock().unwrap();
+                                        record_auto_switch(
+                                            &mut state_guard,
+                                            HardwareFanMode::Bios,
+                                            now,
+                                        );
+                                    }
+                                }
                             }
                         }
                     } else {
@@ -434,6 +1787,7 @@ fn start_temperature_monitor(state: Arc<Mutex<DaemonState>>) {
                             state_guard.consecutive_low_temps = 0;
                             debug!("Temperature not low enough, resetting low temperature counter");
                         }
+                        state_guard.spin_down_since = None;
                     }
                 }
             }
@@ -497,12 +1851,24 @@ fn handle_client(mut stream: UnixStream, state: Arc<Mutex<DaemonState>>) -> Resu
 }
 
 fn main() {
+    let matches = <AppConfig as CommandFactory>::command()
+        .subcommand(doctor::command())
+        .get_matches();
+    let doctor_matches = matches.subcommand_matches("doctor").cloned();
+
     // Initialize tracing subscriber for structured logging
+    // Logs go to stderr so `doctor --json` output stays parseable
+    let default_filter = if doctor_matches.is_some() {
+        "warn"
+    } else {
+        "info"
+    };
     tracing_subscriber::fmt()
         .with_env_filter(
             tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new(default_filter)),
         )
+        .with_writer(io::stderr)
         .with_target(true)
         .with_thread_ids(true)
         .with_file(true)
@@ -510,50 +1876,575 @@ fn main() {
         .init();
 
     let config_path = PathBuf::from(CONFIG_FILE_PATH);
-    let config_opt = if config_path.exists() {
+    let config_value: Option<serde_yaml::Value> = if config_path.exists() {
         let config_str = fs::read_to_string(&config_path).unwrap();
         Some(serde_yaml::from_str(&config_str).unwrap())
     } else {
         None
     };
+    let config_opt = config_value
+        .clone()
+        .map(|value| serde_yaml::from_value(value).unwrap());
     info!("Using config file: {:?}", config_path);
     info!("Using config: {:?}", config_opt);
-    let matches = <AppConfig as CommandFactory>::command().get_matches();
-    let opts = AppConfig::from_merged(matches, config_opt);
+    let mut opts = AppConfig::from_merged(matches.clone(), config_opt);
+
+    // Board quirks only fill in values not set on the command line or in the config file
+    let board_name = hardware::read_board_name(&opts.sysfs_root).ok();
+    let quirk = board_name.as_deref().and_then(quirks::lookup);
+    match (&board_name, quirk) {
+        (Some(board), Some(quirk)) => {
+            info!("Board {} matches quirk: {}", board, quirk.description);
+            quirk.apply_defaults(&mut opts, |field| {
+                matches.value_source(field) == Some(ValueSource::CommandLine)
+                    || config_value
+                        .as_ref()
+                        .and_then(|value| value.get(field))
+                        .is_some()
+            });
+        }
+        (Some(board), None) => info!("No quirk entry for board {}", board),
+        (None, _) => warn!("Failed to read DMI board name, no board quirks applied"),
+    }
+
+    if let Some(doctor_matches) = doctor_matches {
+        let passed = doctor::run(&opts, &doctor_matches);
+        std::process::exit(if passed { 0 } else { 1 });
+    }
 
     info!(
-        "Daemon starting with config: temp_threshold_high={}°C, temp_threshold_low={}°C, consecutive_high_temp_limit={}, consecutive_low_temp_limit={}, temp_check_interval={}s, max_fan_write_interval={:?}",
+        "Daemon starting with config: temp_threshold_high={}°C, temp_threshold_low={}°C, consecutive_high_temp_limit={}, consecutive_low_temp_limit={}, temp_check_interval={}s, max_fan_write_interval={:?}, sysfs_root={:?}, fan_channels={:?}, temp_sensors={:?}, temp_aggregation={:?}, startup_fan_mode={:?}, startup_performance_mode={:?}, kbd_backlight_idle_timeout={:?}, state_dir={:?}, ac_profile={:?}, battery_profile={:?}, cpu_policies={:?}",
         opts.temp_threshold_high,
         opts.temp_threshold_low,
         opts.consecutive_high_temp_limit,
         opts.consecutive_low_temp_limit,
         opts.temp_check_interval,
-        opts.max_fan_write_interval
+        opts.max_fan_write_interval,
+        opts.sysfs_root,
+        opts.fan_channels,
+        opts.temp_sensors,
+        opts.temp_aggregation,
+        opts.startup_fan_mode,
+        opts.startup_performance_mode,
+        opts.kbd_backlight_idle_timeout,
+        opts.state_dir,
+        opts.ac_profile,
+        opts.battery_profile,
+        opts.cpu_policies
     );
     info!("Starting Omenix Fan Control Daemon");
+    let shutdown_signals = block_shutdown_signals();
 
-    // Check if running as root
-    if unsafe { libc::geteuid() } != 0 {
+    // Check if running as root (not needed when pointed at a fake sysfs tree)
+    if opts.sysfs_root == Path::new("/") && unsafe { libc::geteuid() } != 0 {
         error!("Daemon must be run as root to access fan controls");
         std::process::exit(1);
     }
 
-    let state = Arc::new(Mutex::new(DaemonState::new(&opts)));
+    let hardware: Arc<dyn HardwareBackend> = Arc::new(
+        SysfsBackend::new(&opts.sysfs_root, opts.fan_channels.clone())
+            .with_manual_duty(quirk.is_none_or(|quirk| quirk.manual_duty)),
+    );
+    match hardware.fan_channels() {
+        Ok(channels) => info!("Controlling fan channels: {:?}", channels),
+        Err(e) => warn!("No fan control channels detected: {}", e),
+    }
+    let state = Arc::new(Mutex::new(DaemonState::new(
+        &opts, hardware, board_name, quirk,
+    )));
 
-    // Apply initial fan mode (Auto) during startup
-    info!("Applying initial Auto fan mode during daemon startup");
-    if let Err(e) = set_fan_mode(state.clone(), FanMode::Auto) {
-        error!("Failed to set initial fan mode: {}", e);
+    // Capture what to put back on exit before anything is changed
+    let exit_restore = {
+        let state_guard = state.lock().unwrap();
+        ExitRestore::new(
+            state_guard.hardware.clone(),
+            opts.shutdown_fan_mode,
+            state_guard.boot_fan_mode,
+            state_guard.boot_performance_mode,
+            state_guard.cpu_policy_boot.clone(),
+        )
+    };
+    install_panic_restore(state.clone(), exit_restore.clone());
+
+    // Forced startup modes win over the ones saved from the last run
+    let store = StateStore::new(&opts.state_dir);
+    let mut chosen_fan_mode = match opts.startup_fan_mode {
+        Some(mode) => Some(mode),
+        None => {
+            let saved = store.load::<FanMode>(FAN_MODE_KEY);
+            if let Some(mode) = saved {
+                info!("Restoring saved fan mode: {}", mode);
+            }
+            saved
+        }
+    };
+    let startup_fan_mode = chosen_fan_mode.unwrap_or(FanMode::Auto);
+    let startup_performance_mode = match opts.startup_performance_mode {
+        Some(mode) => Some(mode),
+        None => {
+            let saved = store.load::<PerformanceMode>(PERFORMANCE_MODE_KEY);
+            if let Some(mode) = saved {
+                info!("Restoring saved platform profile: {}", mode);
+            }
+            saved
+        }
+    };
+
+    // Apply initial fan mode during startup
+    info!(
+        "Applying initial {} fan mode during daemon startup",
+        startup_fan_mode
+    );
+    if let Err(e) = set_fan_mode(state.clone(), startup_fan_mode) {
+        error!("Failed to set initial {} fan mode: {}", startup_fan_mode, e);
+        // A saved mode this board cannot apply would fail again on every start
+        if opts.startup_fan_mode.is_none() && chosen_fan_mode.is_some() {
+            match store.remove(FAN_MODE_KEY) {
+                Ok(()) => info!("Dropped saved fan mode {}", startup_fan_mode),
+                Err(e) => warn!("Failed to drop saved fan mode: {}", e),
+            }
+        }
+        // Never leave the fans without anything watching the temperature
+        if startup_fan_mode != FanMode::Auto {
+            warn!("Falling back to auto fan mode");
+            chosen_fan_mode = None;
+            if let Err(e) = set_fan_mode(state.clone(), FanMode::Auto) {
+                error!("Failed to set auto fan mode: {}", e);
+            }
+        }
     } else {
-        info!("Successfully applied initial Auto fan mode");
+        let state_guard = state.lock().unwrap();
+        match state_guard.boot_fan_mode {
+            Some(boot_mode) if boot_mode != state_guard.actual_mode => warn!(
+                "Fan mode at startup was {:?}, changed to {:?} for startup mode {}",
+                boot_mode, state_guard.actual_mode, startup_fan_mode
+            ),
+            _ => {}
+        }
+        info!("Successfully applied initial {} fan mode", startup_fan_mode);
+    }
+
+    // Apply initial platform profile, or keep whatever the hardware has
+    let boot_performance_mode = state.lock().unwrap().boot_performance_mode;
+    match (startup_performance_mode, boot_performance_mode) {
+        (Some(mode), boot_mode) if boot_mode != Some(mode) => {
+            warn!(
+                "Platform profile at startup was {:?}, applying startup profile {}",
+                boot_mode, mode
+            );
+            if let Err(e) = set_performance_mode(state.clone(), mode) {
+                error!("Failed to set initial performance mode: {}", e);
+            }
+        }
+        (Some(mode), _) => {
+            info!("Platform profile already matches startup profile {}", mode);
+            apply_cpu_policy(state.clone(), mode);
+        }
+        (None, Some(mode)) => {
+            info!("Keeping platform profile from startup: {}", mode);
+            apply_cpu_policy(state.clone(), mode);
+        }
+        (None, None) => warn!("Platform profile unknown and no startup profile configured"),
+    }
+
+    // Restore the saved keyboard backlight brightness
+    if let Some(brightness) = store.load::<u32>(KBD_BACKLIGHT_KEY) {
+        match set_keyboard_backlight(state.clone(), brightness) {
+            Ok(_) => info!("Restored keyboard backlight: {}", brightness),
+            Err(e) => error!("Failed to restore keyboard backlight {}: {}", brightness, e),
+        }
     }
 
+    // Restore the saved charge limit
+    match store.load::<ChargeLimit>(CHARGE_LIMIT_KEY) {
+        Some(limit) => {
+            let hardware = state.lock().unwrap().hardware.clone();
+            match hardware.write_charge_limit(limit) {
+                Ok(()) => info!("Restored charge limit: {}", limit),
+                Err(e) => error!("Failed to restore charge limit {}: {}", limit, e),
+            }
+        }
+        None => debug!("No saved charge limit to restore"),
+    }
+
+    // Restore what the daemon changed when it is stopped
+    start_shutdown_handler(state.clone(), exit_restore, shutdown_signals);
+
     // Start temperature monitoring thread
     start_temperature_monitor(state.clone());
 
+    // Start watching for fan mode and platform profile changes made outside the daemon
+    start_external_change_watcher(state.clone());
+
+    // Switch profiles on AC/battery transitions if configured, forced or
+    // restored startup modes win until the first real transition
+    if opts.ac_profile.is_some() || opts.battery_profile.is_some() {
+        let apply_initial = chosen_fan_mode.is_none() && startup_performance_mode.is_none();
+        start_power_source_monitor(state.clone(), apply_initial);
+    }
+
+    // Start keyboard backlight idle monitoring if configured
+    if let Some(timeout) = opts.kbd_backlight_idle_timeout {
+        start_keyboard_backlight_monitor(state.clone(), Duration::from_secs(timeout));
+    }
+
+    // Serve the power-profiles-daemon interface if configured
+    if let Some(bus) = opts.power_profiles_dbus.clone() {
+        ppd::start_service(state.clone(), bus);
+    }
+
     // Start Unix socket server
     if let Err(e) = start_unix_socket_server(state) {
         error!("Failed to start socket server: {}", e);
         std::process::exit(1);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Daemon state over an empty sysfs tree, `args` as given on the command line
+    fn test_state(root: &TempDir, args: &[&str]) -> DaemonState {
+        let config =
+            AppConfig::parse_from(std::iter::once("omenix-daemon").chain(args.iter().copied()));
+        let hardware = Arc::new(SysfsBackend::new(root.path(), Vec::new()));
+        DaemonState::new(&config, hardware, None, None)
+    }
+
+    /// Shared daemon state over the fake hp-wmi tree from the hardware tests
+    fn fake_state(args: &[&str]) -> (TempDir, Arc<Mutex<DaemonState>>) {
+        let root = hardware::tests::fake_sysfs();
+        let state = test_state(&root, args);
+        (root, Arc::new(Mutex::new(state)))
+    }
+
+    fn hardware_fan_mode(state: &Arc<Mutex<DaemonState>>) -> HardwareFanMode {
+        let hardware = state.lock().unwrap().hardware.clone();
+        hardware.read_fan_mode().unwrap()
+    }
+
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn auto_switch_holds_for_the_minimum_time() {
+        let root = tempfile::tempdir().unwrap();
+        let mut state = test_state(
+            &root,
+            &["--auto-min-max-time", "60", "--auto-min-bios-time", "30"],
+        );
+        let t0 = Instant::now();
+        state.auto_switched_at = Some(t0);
+
+        let hold = auto_switch_hold(&mut state, HardwareFanMode::Bios, t0 + secs(10));
+        assert_eq!(hold, Some(t0 + secs(60)));
+        let hold = auto_switch_hold(&mut state, HardwareFanMode::Max, t0 + secs(10));
+        assert_eq!(hold, Some(t0 + secs(30)));
+        assert_eq!(
+            auto_switch_hold(&mut state, HardwareFanMode::Bios, t0 + secs(60)),
+            None
+        );
+    }
+
+    #[test]
+    fn auto_switch_prunes_switches_outside_the_window() {
+        let root = tempfile::tempdir().unwrap();
+        let mut state = test_state(
+            &root,
+            &[
+                "--auto-max-transitions",
+                "2",
+                "--auto-transition-window",
+                "600",
+            ],
+        );
+        let t0 = Instant::now();
+        state.auto_switches = VecDeque::from([t0, t0 + secs(100)]);
+
+        let hold = auto_switch_hold(&mut state, HardwareFanMode::Bios, t0 + secs(599));
+        assert_eq!(hold, Some(t0 + secs(600)));
+        assert_eq!(state.auto_switches.len(), 2);
+
+        let hold = auto_switch_hold(&mut state, HardwareFanMode::Bios, t0 + secs(600));
+        assert_eq!(hold, None);
+        assert_eq!(state.auto_switches, [t0 + secs(100)]);
+    }
+
+    #[test]
+    fn auto_switch_rate_limit_only_blocks_bios() {
+        let root = tempfile::tempdir().unwrap();
+        let mut state = test_state(&root, &["--auto-max-transitions", "2"]);
+        let t0 = Instant::now();
+        state.auto_switches = VecDeque::from([t0, t0 + secs(1)]);
+
+        assert_eq!(
+            auto_switch_hold(&mut state, HardwareFanMode::Max, t0 + secs(2)),
+            None
+        );
+        assert_eq!(
+            auto_switch_hold(&mut state, HardwareFanMode::Bios, t0 + secs(2)),
+            Some(t0 + secs(600))
+        );
+    }
+
+    #[test]
+    fn spin_down_delay_and_minimum_time_take_the_later() {
+        let root = tempfile::tempdir().unwrap();
+        let mut state = test_state(
+            &root,
+            &["--auto-min-max-time", "60", "--auto-spin-down-delay", "20"],
+        );
+        let t0 = Instant::now();
+        state.auto_switched_at = Some(t0);
+
+        // The minimum time on max outlasts the delay
+        assert_eq!(
+            auto_spin_down_hold(&mut state, t0 + secs(10)),
+            Some(t0 + secs(60))
+        );
+        // The delay counts from the first consistently low reading
+        state.spin_down_since = None;
+        assert_eq!(
+            auto_spin_down_hold(&mut state, t0 + secs(50)),
+            Some(t0 + secs(70))
+        );
+        assert_eq!(
+            auto_spin_down_hold(&mut state, t0 + secs(65)),
+            Some(t0 + secs(70))
+        );
+        assert_eq!(auto_spin_down_hold(&mut state, t0 + secs(70)), None);
+    }
+
+    #[test]
+    fn status_values_cannot_split_fields() {
+        let status = format_status(&[
+            (
+                "Error",
+                "Temperature sensor failed: zone0, zone1 unreadable".to_string(),
+            ),
+            ("Alert", "None".to_string()),
+        ]);
+        assert_eq!(
+            status,
+            "Error: Temperature sensor failed: zone0; zone1 unreadable, Alert: None"
+        );
+        assert_eq!(status.split(", ").count(), 2);
+    }
+
+    #[test]
+    fn sensor_failsafe_forces_max_after_the_limit() {
+        let (_root, state) = fake_state(&["--sensor-failure-limit", "3"]);
+        state.lock().unwrap().temp_monitoring_active = true;
+
+        handle_sensor_failure(&state, "zone0 unreadable");
+        handle_sensor_failure(&state, "zone0 unreadable");
+        assert_eq!(state.lock().unwrap().sensor_error, None);
+        assert_eq!(hardware_fan_mode(&state), HardwareFanMode::Bios);
+
+        handle_sensor_failure(&state, "zone0 unreadable");
+        let state_guard = state.lock().unwrap();
+        assert_eq!(
+            state_guard.sensor_error.as_deref(),
+            Some("Temperature sensor failed: zone0 unreadable")
+        );
+        assert_eq!(state_guard.actual_mode, HardwareFanMode::Max);
+        drop(state_guard);
+        assert_eq!(hardware_fan_mode(&state), HardwareFanMode::Max);
+    }
+
+    #[test]
+    fn sensor_failsafe_leaves_the_fans_alone_without_monitoring() {
+        let (_root, state) = fake_state(&["--sensor-failure-limit", "1"]);
+        state.lock().unwrap().temp_monitoring_active = false;
+
+        handle_sensor_failure(&state, "zone0 unreadable");
+        assert!(state.lock().unwrap().sensor_error.is_some());
+        assert_eq!(state.lock().unwrap().actual_mode, HardwareFanMode::Bios);
+        assert_eq!(hardware_fan_mode(&state), HardwareFanMode::Bios);
+    }
+
+    #[test]
+    fn sensor_failsafe_rewrites_max_when_the_interval_is_due() {
+        let (_root, state) = fake_state(&[
+            "--sensor-failure-limit",
+            "1",
+            "--max-fan-write-interval",
+            "100",
+        ]);
+        let hardware = {
+            let mut state_guard = state.lock().unwrap();
+            state_guard.temp_monitoring_active = true;
+            state_guard.actual_mode = HardwareFanMode::Max;
+            state_guard.last_fan_write = Some(Instant::now());
+            state_guard.hardware.clone()
+        };
+
+        // The firmware resets the fans, the last write is too recent to repeat
+        hardware.write_fan_mode(HardwareFanMode::Bios).unwrap();
+        handle_sensor_failure(&state, "zone0 unreadable");
+        assert_eq!(hardware_fan_mode(&state), HardwareFanMode::Bios);
+
+        let last_write = Instant::now().checked_sub(secs(200)).unwrap();
+        state.lock().unwrap().last_fan_write = Some(last_write);
+        handle_sensor_failure(&state, "zone0 unreadable");
+        assert_eq!(hardware_fan_mode(&state), HardwareFanMode::Max);
+        assert!(state.lock().unwrap().last_fan_write > Some(last_write));
+    }
+
+    #[test]
+    fn sensor_recovery_clears_the_failsafe() {
+        let (_root, state) = fake_state(&["--sensor-failure-limit", "1"]);
+        state.lock().unwrap().temp_monitoring_active = true;
+        handle_sensor_failure(&state, "zone0 unreadable");
+        assert!(state.lock().unwrap().sensor_error.is_some());
+
+        recover_from_sensor_failure(&state);
+        let state_guard = state.lock().unwrap();
+        assert_eq!(state_guard.sensor_failures, 0);
+        assert_eq!(state_guard.sensor_error, None);
+        // Bringing the fans back down is left to the monitor
+        assert_eq!(state_guard.actual_mode, HardwareFanMode::Max);
+    }
+
+    const CPU0_MAX_FREQ: &str = "sys/devices/system/cpu/cpu0/cpufreq/scaling_max_freq";
+    const PLATFORM_PROFILE: &str = "sys/firmware/acpi/platform_profile";
+
+    /// Fake tree with a 3 GHz CPU, critical at 95°C with a 1 s step
+    fn critical_state(extra_args: &[&str]) -> (TempDir, Arc<Mutex<DaemonState>>) {
+        let mut args = vec![
+            "--temp-critical",
+            "95",
+            "--temp-critical-duration",
+            "1",
+            "--critical-cpu-max-freq",
+            "2000",
+        ];
+        args.extend_from_slice(extra_args);
+        let (root, state) = fake_state(&args);
+        let cpufreq = root.path().join(CPU0_MAX_FREQ);
+        fs::create_dir_all(cpufreq.parent().unwrap()).unwrap();
+        fs::write(&cpufreq, "3000000\n").unwrap();
+        state.lock().unwrap().actual_mode = HardwareFanMode::Max;
+        (root, state)
+    }
+
+    fn read_fake(root: &TempDir, relative: &str) -> String {
+        fs::read_to_string(root.path().join(relative))
+            .unwrap()
+            .trim()
+            .to_string()
+    }
+
+    /// Pretends the temperature has been critical for `elapsed`
+    fn critical_for(state: &Arc<Mutex<DaemonState>>, elapsed: Duration) {
+        state.lock().unwrap().critical_since = Instant::now().checked_sub(elapsed);
+    }
+
+    #[test]
+    fn critical_escalation_needs_the_fans_at_max() {
+        let (root, state) = critical_state(&[]);
+        state.lock().unwrap().actual_mode = HardwareFanMode::Bios;
+        handle_critical_temperature(&state, 99000, 95);
+        handle_critical_temperature(&state, 99000, 95);
+        assert_eq!(state.lock().unwrap().critical_since, None);
+        assert_eq!(read_fake(&root, PLATFORM_PROFILE), "balanced");
+    }
+
+    #[test]
+    fn critical_escalation_takes_each_step_once() {
+        let marker_dir = tempfile::tempdir().unwrap();
+        let marker = marker_dir.path().join("ran");
+        let command = format!("echo ran >> {}", marker.display());
+        let (root, state) = critical_state(&["--critical-command", &command]);
+
+        handle_critical_temperature(&state, 99000, 95);
+        assert_eq!(state.lock().unwrap().critical_stage, None);
+
+        critical_for(&state, Duration::from_millis(1500));
+        handle_critical_temperature(&state, 99000, 95);
+        assert_eq!(
+            state.lock().unwrap().critical_stage,
+            Some(CriticalStage::PowerSaver)
+        );
+        assert_eq!(read_fake(&root, PLATFORM_PROFILE), "low-power");
+        assert_eq!(read_fake(&root, CPU0_MAX_FREQ), "3000000");
+
+        critical_for(&state, Duration::from_millis(3500));
+        handle_critical_temperature(&state, 99000, 95);
+        assert_eq!(
+            state.lock().unwrap().critical_stage,
+            Some(CriticalStage::Command)
+        );
+        assert_eq!(read_fake(&root, CPU0_MAX_FREQ), "2000000");
+        assert!(state.lock().unwrap().critical_alert.is_some());
+
+        // Steps already taken are not repeated while it stays critical
+        fs::write(root.path().join(CPU0_MAX_FREQ), "3000000\n").unwrap();
+        handle_critical_temperature(&state, 99000, 95);
+        assert_eq!(read_fake(&root, CPU0_MAX_FREQ), "3000000");
+
+        let deadline = Instant::now() + secs(5);
+        while !marker.exists() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(20));
+        }
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(fs::read_to_string(&marker).unwrap(), "ran\n");
+    }
+
+    #[test]
+    fn critical_recovery_waits_for_the_margin() {
+        let (root, state) = critical_state(&[]);
+        critical_for(&state, Duration::from_millis(2500));
+        handle_critical_temperature(&state, 99000, 95);
+        assert_eq!(read_fake(&root, PLATFORM_PROFILE), "low-power");
+        assert_eq!(read_fake(&root, CPU0_MAX_FREQ), "2000000");
+
+        // Below critical but within the margin, the steps stay in effect
+        handle_critical_temperature(&state, 92000, 95);
+        assert_eq!(
+            state.lock().unwrap().critical_stage,
+            Some(CriticalStage::CpuCap)
+        );
+        assert_eq!(state.lock().unwrap().critical_since, None);
+        assert_eq!(read_fake(&root, PLATFORM_PROFILE), "low-power");
+
+        handle_critical_temperature(&state, 89000, 95);
+        let state_guard = state.lock().unwrap();
+        assert_eq!(state_guard.critical_stage, None);
+        assert_eq!(state_guard.critical_alert, None);
+        assert_eq!(
+            state_guard.performance_mode,
+            Some(PerformanceMode::Balanced)
+        );
+        drop(state_guard);
+        assert_eq!(read_fake(&root, PLATFORM_PROFILE), "balanced");
+        assert_eq!(read_fake(&root, CPU0_MAX_FREQ), "3000000");
+    }
+
+    #[test]
+    fn critical_recovery_keeps_a_profile_changed_since() {
+        let (root, state) = critical_state(&[]);
+        critical_for(&state, Duration::from_millis(1500));
+        handle_critical_temperature(&state, 99000, 95);
+        assert_eq!(read_fake(&root, PLATFORM_PROFILE), "low-power");
+
+        set_performance_mode(state.clone(), PerformanceMode::Performance).unwrap();
+        handle_critical_temperature(&state, 80000, 95);
+        assert_eq!(state.lock().unwrap().critical_stage, None);
+        assert_eq!(read_fake(&root, PLATFORM_PROFILE), "performance");
+    }
+
+    #[test]
+    fn fan_writes_are_refused_once_shutting_down() {
+        let (_root, state) = fake_state(&[]);
+        state.lock().unwrap().shutting_down = true;
+
+        assert!(set_fan_mode(state.clone(), FanMode::Max).is_err());
+        state.lock().unwrap().temp_monitoring_active = true;
+        handle_sensor_failure(&state, "zone0 unreadable");
+        assert_eq!(hardware_fan_mode(&state), HardwareFanMode::Bios);
+    }
+}
@@ -0,0 +1,58 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tracing::{debug, warn};
+
+/// Settings the kernel forgets across reboots, one file per key below `dir`
+#[derive(Debug, Clone)]
+pub struct StateStore {
+    dir: PathBuf,
+}
+
+impl StateStore {
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Returns None if the key was never saved or does not parse
+    pub fn load<T: FromStr>(&self, key: &str) -> Option<T>
+    where
+        T::Err: fmt::Display,
+    {
+        let path = self.dir.join(key);
+        let value = fs::read_to_string(&path).ok()?;
+        match value.trim().parse::<T>() {
+            Ok(value) => {
+                debug!("Loaded {} from {:?}", key, path);
+                Some(value)
+            }
+            Err(e) => {
+                warn!("Ignoring invalid saved {} in {:?}: {}", key, path, e);
+                None
+            }
+        }
+    }
+
+    pub fn save<T: fmt::Display>(&self, key: &str, value: &T) -> Result<(), io::Error> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(key);
+        // Write to a temporary file first so a crash never leaves a truncated value
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, format!("{}\n", value))?;
+        fs::rename(&tmp_path, &path)?;
+        debug!("Saved {} to {:?}", key, path);
+        Ok(())
+    }
+
+    /// Forgets a saved value, a key that was never saved is not an error
+    pub fn remove(&self, key: &str) -> Result<(), io::Error> {
+        match fs::remove_file(self.dir.join(key)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use tracing::debug;
+
+/// PID controller driving fan duty towards a target temperature, the output
+/// is clamped to `min_duty..=max_duty` and the integral stops accumulating
+/// while the output is saturated so it does not wind up
+#[derive(Debug, Clone)]
+pub struct PidController {
+    setpoint: f64,
+    kp: f64,
+    ki: f64,
+    kd: f64,
+    min_duty: f64,
+    max_duty: f64,
+    integral: f64,
+    last_temp: Option<f64>,
+}
+
+impl PidController {
+    /// Gains are in percent duty per °C (kp), per °C·s (ki) and per °C/s (kd)
+    pub fn new(setpoint: i32, kp: f64, ki: f64, kd: f64, min_duty: u8, max_duty: u8) -> Self {
+        let max_duty = f64::from(max_duty.min(100));
+        let min_duty = f64::from(min_duty).min(max_duty);
+        Self {
+            setpoint: f64::from(setpoint),
+            kp,
+            ki,
+            kd,
+            min_duty,
+            max_duty,
+            integral: min_duty,
+            last_temp: None,
+        }
+    }
+
+    /// Highest duty the controller outputs, in percent
+    pub fn max_duty(&self) -> u8 {
+        self.max_duty as u8
+    }
+
+    /// Starts over from `duty` so taking over from another mode does not
+    /// cause a jump in fan speed
+    pub fn reset(&mut self, duty: u8) {
+        self.integral = f64::from(duty).clamp(self.min_duty, self.max_duty);
+        self.last_temp = None;
+    }
+
+    /// Duty in percent for a temperature in °C measured `dt` seconds after the
+    /// previous one
+    pub fn update(&mut self, temp: f64, dt: f64) -> u8 {
+        let error = temp - self.setpoint;
+        // Derivative on the measurement rather than the error, the setpoint
+        // never changes at runtime but sensor steps would otherwise kick
+        let derivative = match self.last_temp {
+            Some(last_temp) if dt > 0.0 => (temp - last_temp) / dt,
+            _ => 0.0,
+        };
+        self.last_temp = Some(temp);
+
+        let proportional = self.kp * error;
+        let candidate = self.integral + self.ki * error * dt;
+        let unclamped = proportional + candidate + self.kd * derivative;
+        let output = unclamped.clamp(self.min_duty, self.max_duty);
+
+        // Only integrate when it does not push further into saturation
+        let saturated_high = unclamped > self.max_duty && error > 0.0;
+        let saturated_low = unclamped < self.min_duty && error < 0.0;
+        if !saturated_high && !saturated_low {
+            self.integral = candidate.clamp(self.min_duty, self.max_duty);
+        }
+
+        debug!(
+            "PID: {:.1}°C (setpoint {:.0}°C) P={:.1} I={:.1} D={:.1} -> {:.0}%",
+            temp,
+            self.setpoint,
+            proportional,
+            self.integral,
+            self.kd * derivative,
+            output
+        );
+        output.round() as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_stays_within_the_duty_range() {
+        let mut pid = PidController::new(70, 4.0, 0.2, 1.0, 20, 80);
+        assert_eq!(pid.update(30.0, 1.0), 20);
+        assert_eq!(pid.update(100.0, 1.0), 80);
+    }
+
+    #[test]
+    fn proportional_term_scales_with_the_error() {
+        let mut pid = PidController::new(70, 4.0, 0.0, 0.0, 0, 100);
+        pid.reset(40);
+        assert_eq!(pid.update(75.0, 1.0), 60);
+        assert_eq!(pid.update(65.0, 1.0), 20);
+    }
+
+    #[test]
+    fn integral_removes_steady_state_error() {
+        let mut pid = PidController::new(70, 0.0, 1.0, 0.0, 0, 100);
+        pid.reset(40);
+        assert_eq!(pid.update(72.0, 5.0), 50);
+        assert_eq!(pid.update(72.0, 5.0), 60);
+        // At the setpoint the accumulated duty is held
+        assert_eq!(pid.update(70.0, 5.0), 60);
+    }
+
+    #[test]
+    fn derivative_acts_on_the_measurement() {
+        let mut pid = PidController::new(70, 0.0, 0.0, 2.0, 0, 100);
+        pid.reset(50);
+        // No previous reading, no derivative kick
+        assert_eq!(pid.update(70.0, 1.0), 50);
+        assert_eq!(pid.update(75.0, 1.0), 60);
+        assert_eq!(pid.update(75.0, 1.0), 50);
+    }
+
+    #[test]
+    fn saturation_does_not_wind_up_the_integral() {
+        let mut pid = PidController::new(70, 1.0, 1.0, 0.0, 20, 100);
+        pid.reset(100);
+        for _ in 0..100 {
+            assert_eq!(pid.update(95.0, 1.0), 100);
+        }
+        // Back below the setpoint the fans slow down straight away
+        assert!(pid.update(65.0, 1.0) < 100);
+    }
+
+    #[test]
+    fn reset_clamps_the_starting_duty() {
+        let mut pid = PidController::new(70, 0.0, 0.0, 0.0, 30, 90);
+        pid.reset(100);
+        assert_eq!(pid.update(70.0, 1.0), 90);
+        pid.reset(0);
+        assert_eq!(pid.update(70.0, 1.0), 30);
+    }
+}
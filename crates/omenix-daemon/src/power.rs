@@ -0,0 +1,54 @@
+use std::fmt;
+use std::str::FromStr;
+
+use omenix_lib::impl_serde_via_str;
+use omenix_lib::types::{FanMode, PerformanceMode};
+
+/// Where the laptop is currently drawing power from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PowerSource {
+    Ac,
+    Battery,
+}
+
+impl fmt::Display for PowerSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PowerSource::Ac => write!(f, "AC"),
+            PowerSource::Battery => write!(f, "Battery"),
+        }
+    }
+}
+
+/// Fan and performance mode applied when switching to a power source
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PowerProfile {
+    pub fan_mode: FanMode,
+    pub performance_mode: PerformanceMode,
+}
+
+impl fmt::Display for PowerProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.fan_mode, self.performance_mode)
+    }
+}
+
+impl FromStr for PowerProfile {
+    type Err = String;
+
+    /// Parses `fan_mode:performance_mode`, e.g. `auto:performance` or `manual 40:power-saver`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (fan_mode, performance_mode) = s.split_once(':').ok_or_else(|| {
+            format!(
+                "Invalid power profile (expected fan_mode:performance_mode): {}",
+                s
+            )
+        })?;
+        Ok(PowerProfile {
+            fan_mode: fan_mode.trim().parse()?,
+            performance_mode: performance_mode.trim().parse()?,
+        })
+    }
+}
+
+impl_serde_via_str!(PowerProfile);
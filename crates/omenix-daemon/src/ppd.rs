@@ -0,0 +1,343 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tracing::{debug, error, info, warn};
+use zbus::blocking::connection;
+use zbus::zvariant::{OwnedValue, Str};
+
+use crate::{DaemonState, EXTERNAL_CHANGE_CHECK_INTERVAL};
+use omenix_lib::types::PerformanceMode;
+
+const BUS_NAME: &str = "net.hadess.PowerProfiles";
+const OBJECT_PATH: &str = "/net/hadess/PowerProfiles";
+const DRIVER_NAME: &str = "omenix";
+
+/// power-profiles-daemon profiles and the platform profiles that can back
+/// them, in order of preference
+const PROFILE_MODES: [(&str, &[PerformanceMode]); 3] = [
+    (
+        "power-saver",
+        &[
+            PerformanceMode::PowerSaver,
+            PerformanceMode::LowPower,
+            PerformanceMode::Quiet,
+            PerformanceMode::Cool,
+        ],
+    ),
+    (
+        "balanced",
+        &[
+            PerformanceMode::Balanced,
+            PerformanceMode::BalancedPerformance,
+        ],
+    ),
+    (
+        "performance",
+        &[PerformanceMode::Performance, PerformanceMode::MaxPower],
+    ),
+];
+
+/// Platform profile selected for a power-profiles-daemon profile, None if
+/// the hardware has nothing to back it with
+fn mode_for_profile(profile: &str, choices: &[PerformanceMode]) -> Option<PerformanceMode> {
+    let (_, modes) = PROFILE_MODES.iter().find(|(name, _)| *name == profile)?;
+    let choices = if choices.is_empty() {
+        &PerformanceMode::DEFAULTS[..]
+    } else {
+        choices
+    };
+    modes.iter().copied().find(|mode| choices.contains(mode))
+}
+
+/// power-profiles-daemon profile reported for a platform profile
+fn profile_for_mode(mode: PerformanceMode) -> &'static str {
+    PROFILE_MODES
+        .iter()
+        .find(|(_, modes)| modes.contains(&mode))
+        .map_or("balanced", |(name, _)| name)
+}
+
+/// Profile for the daemon's performance mode, `balanced` while it is unknown
+fn profile_for_state(state: &Arc<Mutex<DaemonState>>) -> &'static str {
+    state
+        .lock()
+        .unwrap()
+        .performance_mode
+        .map_or("balanced", profile_for_mode)
+}
+
+/// `net.hadess.PowerProfiles` backed by the daemon's performance mode, so
+/// desktop power menus go through the daemon instead of racing it for
+/// `platform_profile`
+struct PowerProfiles {
+    state: Arc<Mutex<DaemonState>>,
+}
+
+#[zbus::interface(name = "net.hadess.PowerProfiles")]
+impl PowerProfiles {
+    #[zbus(property)]
+    fn active_profile(&self) -> String {
+        profile_for_state(&self.state).to_string()
+    }
+
+    #[zbus(property)]
+    fn set_active_profile(&self, profile: String) -> zbus::fdo::Result<()> {
+        let choices = self.state.lock().unwrap().performance_choices.clone();
+        let mode = mode_for_profile(&profile, &choices).ok_or_else(|| {
+            zbus::fdo::Error::InvalidArgs(format!("Profile {} is not available", profile))
+        })?;
+        info!("D-Bus client requested profile {} ({})", profile, mode);
+        crate::set_performance_mode(self.state.clone(), mode).map_err(zbus::fdo::Error::Failed)?;
+        crate::save_user_setting(&self.state, crate::PERFORMANCE_MODE_KEY, &mode);
+        Ok(())
+    }
+
+    #[zbus(property)]
+    fn profiles(&self) -> Vec<HashMap<String, OwnedValue>> {
+        let choices = self.state.lock().unwrap().performance_choices.clone();
+        PROFILE_MODES
+            .iter()
+            .filter_map(|(name, _)| {
+                let mode = mode_for_profile(name, &choices)?;
+                let mut profile = HashMap::new();
+                profile.insert("Profile".to_string(), OwnedValue::from(Str::from(*name)));
+                profile.insert(
+                    "Driver".to_string(),
+                    OwnedValue::from(Str::from(DRIVER_NAME)),
+                );
+                profile.insert(
+                    "PlatformDriver".to_string(),
+                    OwnedValue::from(Str::from(DRIVER_NAME)),
+                );
+                profile.insert(
+                    "PlatformProfile".to_string(),
+                    OwnedValue::from(Str::from(mode.to_string())),
+                );
+                Some(profile)
+            })
+            .collect()
+    }
+
+    /// Always empty, the firmware does not report thermal throttling
+    #[zbus(property)]
+    fn performance_degraded(&self) -> String {
+        String::new()
+    }
+
+    /// Deprecated upstream, kept for older clients
+    #[zbus(property)]
+    fn performance_inhibited(&self) -> String {
+        String::new()
+    }
+
+    #[zbus(property)]
+    fn actions(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    #[zbus(property)]
+    fn version(&self) -> String {
+        env!("CARGO_PKG_VERSION").to_string()
+    }
+}
+
+/// Serves `net.hadess.PowerProfiles` on `bus` (`system` or a D-Bus address)
+/// and announces performance mode changes made elsewhere
+pub fn start_service(state: Arc<Mutex<DaemonState>>, bus: String) {
+    thread::spawn(move || {
+        let builder = match bus.as_str() {
+            "system" => connection::Builder::system(),
+            address => connection::Builder::address(address),
+        };
+        let connection = match builder
+            .and_then(|builder| builder.name(BUS_NAME))
+            .and_then(|builder| {
+                builder.serve_at(
+                    OBJECT_PATH,
+                    PowerProfiles {
+                        state: state.clone(),
+                    },
+                )
+            })
+            .and_then(|builder| builder.build())
+        {
+            Ok(connection) => connection,
+            Err(e) => {
+                error!(
+                    "Failed to serve {} on D-Bus {} (is power-profiles-daemon running?): {}",
+                    BUS_NAME, bus, e
+                );
+                return;
+            }
+        };
+        info!("Serving {} on D-Bus {}", BUS_NAME, bus);
+
+        let iface = match connection
+            .object_server()
+            .interface::<_, PowerProfiles>(OBJECT_PATH)
+        {
+            Ok(iface) => iface,
+            Err(e) => {
+                error!("Failed to look up {} interface: {}", BUS_NAME, e);
+                return;
+            }
+        };
+
+        // Socket clients, power profiles and the Omen key change the mode
+        // without going through D-Bus, so poll for changes to announce
+        let mut announced = profile_for_state(&state);
+        loop {
+            thread::sleep(EXTERNAL_CHANGE_CHECK_INTERVAL);
+            let current = profile_for_state(&state);
+            if current == announced {
+                continue;
+            }
+            debug!("Announcing profile change: {} -> {}", announced, current);
+            let iface_guard = iface.get();
+            if let Err(e) =
+                zbus::block_on(iface_guard.active_profile_changed(iface.signal_emitter()))
+            {
+                warn!("Failed to announce profile change: {}", e);
+            }
+            announced = current;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AppConfig;
+    use crate::hardware::{HardwareBackend, SysfsBackend};
+    use clap::Parser;
+    use std::fs;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+    use std::time::{Duration, Instant};
+    use zbus::proxy::CacheProperties;
+
+    #[test]
+    fn profiles_map_onto_available_modes() {
+        let choices = [
+            PerformanceMode::LowPower,
+            PerformanceMode::Balanced,
+            PerformanceMode::Performance,
+        ];
+        assert_eq!(
+            mode_for_profile("power-saver", &choices),
+            Some(PerformanceMode::LowPower)
+        );
+        assert_eq!(
+            mode_for_profile("performance", &choices),
+            Some(PerformanceMode::Performance)
+        );
+        assert_eq!(
+            mode_for_profile("power-saver", &[]),
+            Some(PerformanceMode::PowerSaver)
+        );
+        assert_eq!(
+            mode_for_profile("performance", &[PerformanceMode::Balanced]),
+            None
+        );
+        assert_eq!(mode_for_profile("turbo", &choices), None);
+    }
+
+    #[test]
+    fn modes_map_back_onto_profiles() {
+        assert_eq!(profile_for_mode(PerformanceMode::Cool), "power-saver");
+        assert_eq!(
+            profile_for_mode(PerformanceMode::BalancedPerformance),
+            "balanced"
+        );
+        assert_eq!(profile_for_mode(PerformanceMode::MaxPower), "performance");
+        assert_eq!(profile_for_mode(PerformanceMode::Custom), "balanced");
+    }
+
+    /// Private message bus, stopped when the test ends
+    struct PrivateBus(Child);
+
+    impl Drop for PrivateBus {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    #[test]
+    #[ignore = "needs dbus-daemon"]
+    fn active_profile_goes_through_the_daemon() {
+        // Run on purpose with --include-ignored, a missing dbus-daemon is a failure
+        let mut child = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("dbus-daemon is needed to start a private bus");
+        let mut address = String::new();
+        BufReader::new(child.stdout.take().unwrap())
+            .read_line(&mut address)
+            .unwrap();
+        let address = address.trim().to_string();
+        let _bus = PrivateBus(child);
+
+        let root = tempfile::tempdir().unwrap();
+        let acpi = root.path().join("sys/firmware/acpi");
+        fs::create_dir_all(&acpi).unwrap();
+        fs::write(acpi.join("platform_profile"), "balanced\n").unwrap();
+        fs::write(
+            acpi.join("platform_profile_choices"),
+            "low-power balanced performance\n",
+        )
+        .unwrap();
+        let hardware: Arc<dyn HardwareBackend> =
+            Arc::new(SysfsBackend::new(root.path(), Vec::new()));
+        let config = AppConfig::parse_from(["omenix-daemon"]);
+        let state = Arc::new(Mutex::new(DaemonState::new(&config, hardware, None, None)));
+        start_service(state.clone(), address.clone());
+
+        let connection = connection::Builder::address(address.as_str())
+            .unwrap()
+            .build()
+            .unwrap();
+        let proxy = zbus::blocking::proxy::Builder::<zbus::blocking::Proxy>::new(&connection)
+            .destination(BUS_NAME)
+            .unwrap()
+            .path(OBJECT_PATH)
+            .unwrap()
+            .interface(BUS_NAME)
+            .unwrap()
+            .cache_properties(CacheProperties::No)
+            .build()
+            .unwrap();
+
+        // The service claims its name from its own thread
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let profile = loop {
+            match proxy.get_property::<String>("ActiveProfile") {
+                Ok(profile) => break profile,
+                Err(_) if Instant::now() < deadline => thread::sleep(Duration::from_millis(50)),
+                Err(e) => panic!("{} did not come up: {}", BUS_NAME, e),
+            }
+        };
+        assert_eq!(profile, "balanced");
+
+        proxy.set_property("ActiveProfile", "power-saver").unwrap();
+        assert_eq!(
+            state.lock().unwrap().performance_mode,
+            Some(PerformanceMode::LowPower)
+        );
+        assert_eq!(
+            fs::read_to_string(acpi.join("platform_profile")).unwrap(),
+            "low-power"
+        );
+        assert_eq!(
+            proxy.get_property::<String>("ActiveProfile").unwrap(),
+            "power-saver"
+        );
+
+        assert!(proxy.set_property("ActiveProfile", "turbo").is_err());
+        assert_eq!(
+            state.lock().unwrap().performance_mode,
+            Some(PerformanceMode::LowPower)
+        );
+    }
+}
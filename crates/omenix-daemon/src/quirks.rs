@@ -0,0 +1,87 @@
+use omenix_lib::types::PerformanceMode;
+use tracing::info;
+
+use crate::AppConfig;
+
+/// Known per-board behaviour, keyed by the DMI board name
+#[derive(Debug)]
+pub struct BoardQuirk {
+    pub board_names: &'static [&'static str],
+    pub description: &'static str,
+    /// Default for `max_fan_write_interval` on boards whose firmware reverts Max mode
+    pub max_fan_write_interval: Option<u64>,
+    /// Default for `fan_channels` (empty keeps every detected channel)
+    pub fan_channels: &'static [&'static str],
+    /// Platform profiles known to work (empty trusts `platform_profile_choices`)
+    pub performance_choices: &'static [PerformanceMode],
+    /// Whether the firmware honours `pwmN` duty writes
+    pub manual_duty: bool,
+}
+
+/// Built-in quirk table, boards not listed here use the plain defaults
+pub const BOARD_QUIRKS: &[BoardQuirk] = &[
+    BoardQuirk {
+        board_names: &["8BAB"],
+        description: "OMEN 8BAB",
+        max_fan_write_interval: Some(100),
+        fan_channels: &[],
+        performance_choices: &[],
+        manual_duty: true,
+    },
+    BoardQuirk {
+        // hp-wmi treats these as timed thermal profile boards
+        board_names: &["8A15", "8A42", "8BAD"],
+        description: "OMEN with timed thermal profiles",
+        max_fan_write_interval: Some(100),
+        fan_channels: &[],
+        performance_choices: &[],
+        manual_duty: true,
+    },
+];
+
+/// Find the quirk entry for a DMI board name
+pub fn lookup(board_name: &str) -> Option<&'static BoardQuirk> {
+    BOARD_QUIRKS.iter().find(|quirk| {
+        quirk
+            .board_names
+            .iter()
+            .any(|name| name.eq_ignore_ascii_case(board_name.trim()))
+    })
+}
+
+impl BoardQuirk {
+    /// Fill in config values the user did not set on the command line or in the config file
+    pub fn apply_defaults(&self, config: &mut AppConfig, user_set: impl Fn(&str) -> bool) {
+        if self.max_fan_write_interval.is_some() && !user_set("max_fan_write_interval") {
+            info!(
+                "Quirk {}: max_fan_write_interval={:?}",
+                self.description, self.max_fan_write_interval
+            );
+            config.max_fan_write_interval = self.max_fan_write_interval;
+        }
+        if !self.fan_channels.is_empty() && !user_set("fan_channels") {
+            info!(
+                "Quirk {}: fan_channels={:?}",
+                self.description, self.fan_channels
+            );
+            config.fan_channels = self.fan_channels.iter().map(|c| c.to_string()).collect();
+        }
+    }
+
+    /// Drop profiles the firmware advertises but that are known not to work
+    pub fn filter_performance_choices(
+        &self,
+        choices: Vec<PerformanceMode>,
+    ) -> Vec<PerformanceMode> {
+        if self.performance_choices.is_empty() {
+            return choices;
+        }
+        if choices.is_empty() {
+            return self.performance_choices.to_vec();
+        }
+        choices
+            .into_iter()
+            .filter(|mode| self.performance_choices.contains(mode))
+            .collect()
+    }
+}
@@ -0,0 +1,263 @@
+use omenix_lib::impl_serde_via_str;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SensorKind {
+    ThermalZone, // /sys/class/thermal/thermal_zone*
+    Hwmon,       // /sys/class/hwmon/hwmon*/temp*_input
+}
+
+/// A single temperature reading from a thermal zone or hwmon sensor
+#[derive(Debug, Clone)]
+pub struct SensorReading {
+    pub kind: SensorKind,
+    /// Thermal zone `type` or hwmon `name` (e.g. `x86_pkg_temp`, `coretemp`)
+    pub name: String,
+    /// hwmon `tempN_label` if present (e.g. `Package id 0`, `Tctl`)
+    pub label: Option<String>,
+    /// Temperature in millicelsius
+    pub temp: i32,
+}
+
+impl SensorReading {
+    /// Human readable identifier used in logs and status
+    pub fn id(&self) -> String {
+        match &self.label {
+            Some(label) => format!("{}/{}", self.name, label),
+            None => self.name.clone(),
+        }
+    }
+}
+
+/// Selects sensors by thermal zone type, hwmon name or hwmon label
+#[derive(Debug, Clone, PartialEq)]
+pub struct SensorSelector {
+    pub name: String,
+    /// Weight used by the weighted aggregation
+    pub weight: f64,
+}
+
+fn default_weight() -> f64 {
+    1.0
+}
+
+impl SensorSelector {
+    fn matches(&self, reading: &SensorReading) -> bool {
+        self.name == reading.name || reading.label.as_deref() == Some(self.name.as_str())
+    }
+}
+
+impl FromStr for SensorSelector {
+    type Err = String;
+
+    /// Parses `name` or `name:weight`, e.g. `x86_pkg_temp:2`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.rsplit_once(':') {
+            Some((name, weight)) => {
+                let weight = weight
+                    .trim()
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|weight| *weight > 0.0)
+                    .ok_or_else(|| format!("Invalid sensor weight: {}", weight))?;
+                Ok(SensorSelector {
+                    name: name.trim().to_string(),
+                    weight,
+                })
+            }
+            None => Ok(SensorSelector {
+                name: s.trim().to_string(),
+                weight: default_weight(),
+            }),
+        }
+    }
+}
+
+impl fmt::Display for SensorSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.weight == default_weight() {
+            write!(f, "{}", self.name)
+        } else {
+            write!(f, "{}:{}", self.name, self.weight)
+        }
+    }
+}
+
+impl_serde_via_str!(SensorSelector);
+
+/// How readings from the selected sensors are combined into one temperature
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum TempAggregation {
+    Max,
+    Average,
+    Weighted,
+}
+
+/// Aggregated temperature and the sensor(s) it came from
+#[derive(Debug, Clone)]
+pub struct TemperatureReading {
+    /// Temperature in millicelsius
+    pub temp: i32,
+    /// Sensor driving the reading (e.g. `x86_pkg_temp` or `average(coretemp/Tctl+amdgpu)`)
+    pub source: String,
+}
+
+/// Combine the readings matched by `selectors` (all thermal zones when empty)
+pub fn aggregate(
+    readings: &[SensorReading],
+    selectors: &[SensorSelector],
+    aggregation: TempAggregation,
+) -> Result<TemperatureReading, io::Error> {
+    let selected: Vec<(&SensorReading, f64)> = readings
+        .iter()
+        .filter_map(|reading| {
+            if selectors.is_empty() {
+                // hwmon sensors mostly duplicate thermal zones, only use them when selected
+                return (reading.kind == SensorKind::ThermalZone)
+                    .then_some((reading, default_weight()));
+            }
+            selectors
+                .iter()
+                .find(|selector| selector.matches(reading))
+                .map(|selector| (reading, selector.weight))
+        })
+        .collect();
+
+    if selected.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("No temperature sensor matches {:?}", selectors),
+        ));
+    }
+
+    let ids: Vec<String> = selected.iter().map(|(reading, _)| reading.id()).collect();
+
+    let reading = match aggregation {
+        TempAggregation::Max => {
+            let (hottest, _) = selected
+                .iter()
+                .max_by_key(|(reading, _)| reading.temp)
+                .expect("selected is not empty");
+            TemperatureReading {
+                temp: hottest.temp,
+                source: hottest.id(),
+            }
+        }
+        TempAggregation::Average => {
+            let sum: i64 = selected
+                .iter()
+                .map(|(reading, _)| i64::from(reading.temp))
+                .sum();
+            TemperatureReading {
+                temp: (sum / selected.len() as i64) as i32,
+                source: format!("average({})", ids.join("+")),
+            }
+        }
+        TempAggregation::Weighted => {
+            let total_weight: f64 = selected.iter().map(|(_, weight)| weight).sum();
+            let weighted_sum: f64 = selected
+                .iter()
+                .map(|(reading, weight)| f64::from(reading.temp) * weight)
+                .sum();
+            TemperatureReading {
+                temp: (weighted_sum / total_weight).round() as i32,
+                source: format!("weighted({})", ids.join("+")),
+            }
+        }
+    };
+
+    Ok(reading)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading(kind: SensorKind, name: &str, label: Option<&str>, temp: i32) -> SensorReading {
+        SensorReading {
+            kind,
+            name: name.to_string(),
+            label: label.map(str::to_string),
+            temp,
+        }
+    }
+
+    fn readings() -> Vec<SensorReading> {
+        vec![
+            reading(SensorKind::ThermalZone, "x86_pkg_temp", None, 80_000),
+            reading(SensorKind::ThermalZone, "acpitz", None, 50_000),
+            reading(SensorKind::Hwmon, "coretemp", Some("Package id 0"), 90_000),
+        ]
+    }
+
+    fn selectors(names: &[&str]) -> Vec<SensorSelector> {
+        names.iter().map(|name| name.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn parses_name_and_weight() {
+        let selector: SensorSelector = "x86_pkg_temp:2".parse().unwrap();
+        assert_eq!(selector.name, "x86_pkg_temp");
+        assert_eq!(selector.weight, 2.0);
+        assert_eq!(selector.to_string(), "x86_pkg_temp:2");
+
+        let selector: SensorSelector = "amdgpu".parse().unwrap();
+        assert_eq!(selector.weight, 1.0);
+        assert_eq!(selector.to_string(), "amdgpu");
+
+        assert!("amdgpu:0".parse::<SensorSelector>().is_err());
+        assert!("amdgpu:heavy".parse::<SensorSelector>().is_err());
+    }
+
+    #[test]
+    fn defaults_to_thermal_zones_only() {
+        let result = aggregate(&readings(), &[], TempAggregation::Max).unwrap();
+        assert_eq!(result.temp, 80_000);
+        assert_eq!(result.source, "x86_pkg_temp");
+    }
+
+    #[test]
+    fn selects_hwmon_sensors_by_label() {
+        let result = aggregate(
+            &readings(),
+            &selectors(&["Package id 0"]),
+            TempAggregation::Max,
+        )
+        .unwrap();
+        assert_eq!(result.temp, 90_000);
+        assert_eq!(result.source, "coretemp/Package id 0");
+    }
+
+    #[test]
+    fn averages_selected_sensors() {
+        let result = aggregate(
+            &readings(),
+            &selectors(&["x86_pkg_temp", "acpitz"]),
+            TempAggregation::Average,
+        )
+        .unwrap();
+        assert_eq!(result.temp, 65_000);
+        assert_eq!(result.source, "average(x86_pkg_temp+acpitz)");
+    }
+
+    #[test]
+    fn weights_selected_sensors() {
+        let result = aggregate(
+            &readings(),
+            &selectors(&["x86_pkg_temp:3", "acpitz"]),
+            TempAggregation::Weighted,
+        )
+        .unwrap();
+        assert_eq!(result.temp, 72_500);
+    }
+
+    #[test]
+    fn unmatched_selectors_are_an_error() {
+        let err = aggregate(&readings(), &selectors(&["nvme"]), TempAggregation::Max).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::sync::Arc;
+use tracing::{error, info, warn};
+
+use crate::cpu::CpuPolicy;
+use crate::hardware::HardwareBackend;
+use omenix_lib::client::DAEMON_SOCKET_PATH;
+use omenix_lib::types::{HardwareFanMode, PerformanceMode};
+
+/// Fan mode written back when the daemon exits
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ShutdownFanMode {
+    /// Hand the fans back to the firmware
+    Bios,
+    /// Whatever the fans were set to when the daemon started
+    Boot,
+}
+
+/// Hardware state to put back on exit, captured at startup so a panic can
+/// use it even while another thread holds the state lock
+#[derive(Debug, Clone)]
+pub struct ExitRestore {
+    pub hardware: Arc<dyn HardwareBackend>,
+    pub fan_mode: HardwareFanMode,
+    pub performance_mode: Option<PerformanceMode>,
+    pub cpu_policy: Option<CpuPolicy>,
+}
+
+impl ExitRestore {
+    pub fn new(
+        hardware: Arc<dyn HardwareBackend>,
+        shutdown_fan_mode: ShutdownFanMode,
+        boot_fan_mode: Option<HardwareFanMode>,
+        boot_performance_mode: Option<PerformanceMode>,
+        cpu_policy_boot: Option<CpuPolicy>,
+    ) -> Self {
+        let fan_mode = match (shutdown_fan_mode, boot_fan_mode) {
+            (ShutdownFanMode::Boot, Some(mode)) => mode,
+            (ShutdownFanMode::Boot, None) => {
+                warn!("Fan mode at startup unknown, BIOS control is restored on exit");
+                HardwareFanMode::Bios
+            }
+            (ShutdownFanMode::Bios, _) => HardwareFanMode::Bios,
+        };
+        Self {
+            hardware,
+            fan_mode,
+            performance_mode: boot_performance_mode,
+            cpu_policy: cpu_policy_boot,
+        }
+    }
+
+    /// Writes everything back and removes the socket, `cpu_max_freqs` are the
+    /// limits saved before a critical temperature CPU cap
+    pub fn run(&self, cpu_max_freqs: Option<Vec<u32>>) {
+        match self.hardware.write_fan_mode(self.fan_mode) {
+            Ok(_) => info!("Restored fan mode: {:?}", self.fan_mode),
+            Err(e) => error!("Failed to restore fan mode {:?}: {}", self.fan_mode, e),
+        }
+        if let Some(mode) = self.performance_mode {
+            match self.hardware.write_performance_mode(mode) {
+                Ok(()) => info!("Restored platform profile: {}", mode),
+                Err(e) => error!("Failed to restore platform profile {}: {}", mode, e),
+            }
+        }
+        if let Some(policy) = &self.cpu_policy {
+            match self.hardware.write_cpu_policy(policy) {
+                Ok(()) => info!("Restored CPU policy: {}", policy),
+                Err(e) => error!("Failed to restore CPU policy: {}", e),
+            }
+        }
+        if let Some(freqs) = cpu_max_freqs {
+            match self.hardware.write_cpu_max_freqs(&freqs) {
+                Ok(()) => info!("Restored CPU frequency limits"),
+                Err(e) => error!("Failed to restore CPU frequency limits: {}", e),
+            }
+        }
+        match fs::remove_file(DAEMON_SOCKET_PATH) {
+            Ok(()) => info!("Removed socket {}", DAEMON_SOCKET_PATH),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => warn!("Failed to remove socket {}: {}", DAEMON_SOCKET_PATH, e),
+        }
+    }
+}
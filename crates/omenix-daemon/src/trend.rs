@@ -0,0 +1,118 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Rate of temperature change over a sliding window of readings
+#[derive(Debug, Clone)]
+pub struct TempTrend {
+    window: Duration,
+    samples: VecDeque<(Instant, i32)>,
+}
+
+impl TempTrend {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Records a reading in millidegrees Celsius and drops readings that
+    /// fell out of the window
+    pub fn push(&mut self, at: Instant, temp: i32) {
+        self.samples.push_back((at, temp));
+        while let Some((oldest, _)) = self.samples.front() {
+            if at.duration_since(*oldest) <= self.window {
+                break;
+            }
+            self.samples.pop_front();
+        }
+    }
+
+    /// Least-squares slope in °C per second, None until the window holds
+    /// two readings
+    pub fn slope(&self) -> Option<f64> {
+        let (start, _) = *self.samples.front()?;
+        if self.samples.len() < 2 {
+            return None;
+        }
+
+        let n = self.samples.len() as f64;
+        let points: Vec<(f64, f64)> = self
+            .samples
+            .iter()
+            .map(|(at, temp)| {
+                (
+                    at.duration_since(start).as_secs_f64(),
+                    f64::from(*temp) / 1000.0,
+                )
+            })
+            .collect();
+        let mean_t = points.iter().map(|(t, _)| t).sum::<f64>() / n;
+        let mean_temp = points.iter().map(|(_, temp)| temp).sum::<f64>() / n;
+        let covariance: f64 = points
+            .iter()
+            .map(|(t, temp)| (t - mean_t) * (temp - mean_temp))
+            .sum();
+        let variance: f64 = points.iter().map(|(t, _)| (t - mean_t).powi(2)).sum();
+        (variance > 0.0).then(|| covariance / variance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn needs_two_readings() {
+        let mut trend = TempTrend::new(Duration::from_secs(10));
+        assert_eq!(trend.slope(), None);
+        trend.push(Instant::now(), 50_000);
+        assert_eq!(trend.slope(), None);
+    }
+
+    #[test]
+    fn fits_a_linear_rise() {
+        let start = Instant::now();
+        let mut trend = TempTrend::new(Duration::from_secs(10));
+        for second in 0..5 {
+            trend.push(
+                start + Duration::from_secs(second),
+                50_000 + second as i32 * 1_500,
+            );
+        }
+        let slope = trend.slope().unwrap();
+        assert!((slope - 1.5).abs() < 1e-9, "slope {}", slope);
+    }
+
+    #[test]
+    fn averages_out_noise() {
+        let start = Instant::now();
+        let mut trend = TempTrend::new(Duration::from_secs(10));
+        for (second, temp) in [50_000, 51_000, 50_000, 51_000].into_iter().enumerate() {
+            trend.push(start + Duration::from_secs(second as u64), temp);
+        }
+        assert!(trend.slope().unwrap().abs() < 0.5);
+    }
+
+    #[test]
+    fn drops_readings_outside_the_window() {
+        let start = Instant::now();
+        let mut trend = TempTrend::new(Duration::from_secs(2));
+        // A fast rise long ago must not count once the temperature is flat
+        trend.push(start, 40_000);
+        trend.push(start + Duration::from_secs(1), 60_000);
+        for second in 5..8 {
+            trend.push(start + Duration::from_secs(second), 60_000);
+        }
+        assert_eq!(trend.slope(), Some(0.0));
+    }
+
+    #[test]
+    fn readings_at_the_same_instant_have_no_slope() {
+        let now = Instant::now();
+        let mut trend = TempTrend::new(Duration::from_secs(10));
+        trend.push(now, 50_000);
+        trend.push(now, 60_000);
+        assert_eq!(trend.slope(), None);
+    }
+}
@@ -2,7 +2,7 @@ use std::io::{Read, Write};
 use std::os::unix::net::UnixStream;
 use tracing::{debug, error, info, warn};
 
-use crate::types::{FanMode, PerformanceMode, SystemState};
+use crate::types::{ChargeLimit, FanMode, KeyboardBacklight, PerformanceMode, SystemState};
 
 pub const DAEMON_SOCKET_PATH: &str = "/tmp/omenix-daemon.sock";
 
@@ -52,10 +52,10 @@ impl DaemonClient {
         } else if response.starts_with("ERROR:") {
             let error_msg = response.strip_prefix("ERROR:").unwrap_or(&response).trim();
             error!("Daemon error: {}", error_msg);
-            Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("Daemon error: {}", error_msg),
-            ))
+            Err(std::io::Error::other(format!(
+                "Daemon error: {}",
+                error_msg
+            )))
         } else {
             warn!("Unexpected response from daemon: {}", response);
             Err(std::io::Error::new(
@@ -78,10 +78,10 @@ impl DaemonClient {
         } else if response.starts_with("ERROR:") {
             let error_msg = response.strip_prefix("ERROR:").unwrap_or(&response).trim();
             warn!("Error getting status: {}", error_msg);
-            Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("Error getting status: {}", error_msg),
-            ))
+            Err(std::io::Error::other(format!(
+                "Error getting status: {}",
+                error_msg
+            )))
         } else {
             warn!("Unexpected response from daemon: {}", response);
             Err(std::io::Error::new(
@@ -104,10 +104,126 @@ impl DaemonClient {
         } else if response.starts_with("ERROR:") {
             let error_msg = response.strip_prefix("ERROR:").unwrap_or(&response).trim();
             error!("Daemon error: {}", error_msg);
+            Err(std::io::Error::other(format!(
+                "Daemon error: {}",
+                error_msg
+            )))
+        } else {
+            warn!("Unexpected response from daemon: {}", response);
             Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("Daemon error: {}", error_msg),
+                std::io::ErrorKind::InvalidData,
+                "Unexpected response from daemon",
             ))
+        }
+    }
+
+    /// Get keyboard backlight brightness via daemon
+    pub fn get_keyboard_backlight(&self) -> Result<KeyboardBacklight, std::io::Error> {
+        debug!("Getting keyboard backlight from daemon");
+
+        let response = self.send_command("get_kbd_backlight")?;
+
+        if response.starts_with("OK:") {
+            let data = response.strip_prefix("OK:").unwrap_or(&response).trim();
+            status_field(data, "Keyboard")
+                .and_then(|backlight| backlight.parse::<KeyboardBacklight>().ok())
+                .ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("Invalid keyboard backlight response: {}", data),
+                    )
+                })
+        } else if response.starts_with("ERROR:") {
+            let error_msg = response.strip_prefix("ERROR:").unwrap_or(&response).trim();
+            warn!("Error getting keyboard backlight: {}", error_msg);
+            Err(std::io::Error::other(format!(
+                "Error getting keyboard backlight: {}",
+                error_msg
+            )))
+        } else {
+            warn!("Unexpected response from daemon: {}", response);
+            Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Unexpected response from daemon",
+            ))
+        }
+    }
+
+    /// Set keyboard backlight brightness via daemon
+    pub fn set_keyboard_backlight(&self, brightness: u32) -> Result<(), std::io::Error> {
+        info!("Setting keyboard backlight to: {}", brightness);
+
+        let command = format!("set_kbd_backlight {}", brightness);
+        let response = self.send_command(&command)?;
+
+        if response.starts_with("OK:") {
+            info!("Successfully set keyboard backlight to: {}", brightness);
+            Ok(())
+        } else if response.starts_with("ERROR:") {
+            let error_msg = response.strip_prefix("ERROR:").unwrap_or(&response).trim();
+            error!("Daemon error: {}", error_msg);
+            Err(std::io::Error::other(format!(
+                "Daemon error: {}",
+                error_msg
+            )))
+        } else {
+            warn!("Unexpected response from daemon: {}", response);
+            Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Unexpected response from daemon",
+            ))
+        }
+    }
+
+    /// Get battery charge limit via daemon
+    pub fn get_charge_limit(&self) -> Result<ChargeLimit, std::io::Error> {
+        debug!("Getting charge limit from daemon");
+
+        let response = self.send_command("get_charge_limit")?;
+
+        if response.starts_with("OK:") {
+            let data = response.strip_prefix("OK:").unwrap_or(&response).trim();
+            status_field(data, "Limit")
+                .and_then(|limit| limit.parse::<ChargeLimit>().ok())
+                .ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("Invalid charge limit response: {}", data),
+                    )
+                })
+        } else if response.starts_with("ERROR:") {
+            let error_msg = response.strip_prefix("ERROR:").unwrap_or(&response).trim();
+            warn!("Error getting charge limit: {}", error_msg);
+            Err(std::io::Error::other(format!(
+                "Error getting charge limit: {}",
+                error_msg
+            )))
+        } else {
+            warn!("Unexpected response from daemon: {}", response);
+            Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Unexpected response from daemon",
+            ))
+        }
+    }
+
+    /// Set battery charge limit via daemon
+    pub fn set_charge_limit(&self, limit: ChargeLimit) -> Result<(), std::io::Error> {
+        info!("Setting charge limit to: {}", limit);
+
+        let command = format!("set_charge_limit {}", limit);
+        let response = self.send_command(&command)?;
+
+        if response.starts_with("OK:") {
+            info!("Successfully set charge limit to: {}", limit);
+            Ok(())
+        } else if response.starts_with("ERROR:") {
+            let error_msg = response.strip_prefix("ERROR:").unwrap_or(&response).trim();
+            error!("Daemon error: {}", error_msg);
+            Err(std::io::Error::other(format!(
+                "Daemon error: {}",
+                error_msg
+            )))
         } else {
             warn!("Unexpected response from daemon: {}", response);
             Err(std::io::Error::new(
@@ -126,38 +242,77 @@ impl DaemonClient {
         if response.starts_with("OK:") {
             let status_data = response.strip_prefix("OK:").unwrap_or(&response).trim();
 
-            // Parse the status response: "Mode: Auto, Actual: Max, Performance: balanced, Temp: 45°C"
-            let fan_mode = if status_data.contains("Mode: Max") {
-                FanMode::Max
-            } else if status_data.contains("Mode: Auto") {
-                FanMode::Auto
-            } else {
-                FanMode::Bios
-            };
+            // Parse the status response: "Mode: Auto, Actual: Max, Performance: balanced, Profiles: low-power/balanced/performance, Temp: 45°C, Sensor: x86_pkg_temp, Fans: 2300/2400 RPM, ..., Keyboard: 2/3, Battery: 64%, Limit: 75-80%, ..., Error: None, Alert: None"
+            let fan_mode = status_field(status_data, "Mode")
+                .and_then(|mode| mode.parse::<FanMode>().ok())
+                .unwrap_or(FanMode::Bios);
 
             // Parse performance mode from status
-            let performance_mode = if status_data.contains("Performance: performance") {
-                PerformanceMode::Performance
-            } else {
-                PerformanceMode::Balanced
-            };
+            let performance_mode = status_field(status_data, "Performance")
+                .and_then(|mode| mode.parse::<PerformanceMode>().ok());
 
-            // Extract temperature if available
-            let temperature = None; // Would be parsed from daemon response
+            // Parse the profiles the hardware supports
+            let performance_choices = status_field(status_data, "Profiles")
+                .map(|profiles| {
+                    profiles
+                        .split('/')
+                        .filter_map(|mode| mode.parse::<PerformanceMode>().ok())
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            // Extract temperature (in millicelsius) if available
+            let temperature = status_field(status_data, "Temp")
+                .and_then(|temp| temp.trim_end_matches("°C").parse::<i32>().ok())
+                .map(|temp| temp * 1000);
+
+            // Extract per-fan RPM readings if available
+            let fan_speeds = status_field(status_data, "Fans")
+                .and_then(|fans| fans.strip_suffix(" RPM"))
+                .map(|fans| {
+                    fans.split('/')
+                        .filter_map(|rpm| rpm.parse::<u32>().ok())
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            // Keyboard backlight is absent on machines without one
+            let keyboard_backlight = status_field(status_data, "Keyboard")
+                .and_then(|backlight| backlight.parse::<KeyboardBacklight>().ok());
+
+            // Battery fields are Unknown on machines without a battery
+            let battery_capacity = status_field(status_data, "Battery")
+                .and_then(|capacity| capacity.trim_end_matches('%').parse::<u8>().ok());
+            let charge_limit = status_field(status_data, "Limit")
+                .and_then(|limit| limit.parse::<ChargeLimit>().ok());
+
+            // Daemon-side failures such as the sensor failsafe
+            let error_message = status_field(status_data, "Error")
+                .filter(|error| *error != "None")
+                .map(|error| error.to_string());
+            let alert = status_field(status_data, "Alert")
+                .filter(|alert| *alert != "None")
+                .map(|alert| alert.to_string());
 
             Ok(SystemState {
                 fan_mode,
                 performance_mode,
+                performance_choices,
                 temperature,
-                error_message: None,
+                fan_speeds,
+                keyboard_backlight,
+                battery_capacity,
+                charge_limit,
+                error_message,
+                alert,
             })
         } else if response.starts_with("ERROR:") {
             let error_msg = response.strip_prefix("ERROR:").unwrap_or(&response).trim();
             error!("Error getting state: {}", error_msg);
-            Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("Error getting state: {}", error_msg),
-            ))
+            Err(std::io::Error::other(format!(
+                "Error getting state: {}",
+                error_msg
+            )))
         } else {
             warn!("Unexpected response from daemon: {}", response);
             Err(std::io::Error::new(
@@ -172,6 +327,14 @@ impl DaemonClient {
     }
 }
 
+/// Look up a `Key: value` field in a comma-separated status line
+fn status_field<'a>(status: &'a str, key: &str) -> Option<&'a str> {
+    status.split(", ").find_map(|field| {
+        let (name, value) = field.split_once(": ")?;
+        (name.trim() == key).then(|| value.trim())
+    })
+}
+
 impl Default for DaemonClient {
     fn default() -> Self {
         Self::new()
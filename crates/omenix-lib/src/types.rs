@@ -1,11 +1,32 @@
 /// Shared types used across the daemon and client
 use std::fmt;
 
+/// Serialize and deserialize via the same strings used by the socket protocol
+#[macro_export]
+macro_rules! impl_serde_via_str {
+    ($ty:ty) => {
+        impl serde::Serialize for $ty {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for $ty {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let s = <String as serde::Deserialize>::deserialize(deserializer)?;
+                s.parse().map_err(serde::de::Error::custom)
+            }
+        }
+    };
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum FanMode {
-    Max,  // Force fans to maximum speed
-    Auto, // Temperature-based automatic control
-    Bios, // Let BIOS handle fan control
+    Max,        // Force fans to maximum speed
+    Auto,       // Temperature-based automatic control
+    Bios,       // Let BIOS handle fan control
+    Manual(u8), // Fixed fan duty in percent
+    Curve,      // Temperature-to-duty curve from the daemon config
 }
 
 impl fmt::Display for FanMode {
@@ -14,6 +35,8 @@ impl fmt::Display for FanMode {
             FanMode::Max => write!(f, "Max"),
             FanMode::Auto => write!(f, "Auto"),
             FanMode::Bios => write!(f, "Bios"),
+            FanMode::Manual(percent) => write!(f, "Manual {}%", percent),
+            FanMode::Curve => write!(f, "Curve"),
         }
     }
 }
@@ -22,20 +45,29 @@ impl std::str::FromStr for FanMode {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.to_lowercase().as_str() {
-            "max" => Ok(FanMode::Max),
-            "auto" => Ok(FanMode::Auto),
-            "bios" => Ok(FanMode::Bios),
+        let lower = s.to_lowercase();
+        match lower.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["max"] => Ok(FanMode::Max),
+            ["auto"] => Ok(FanMode::Auto),
+            ["bios"] => Ok(FanMode::Bios),
+            ["curve"] => Ok(FanMode::Curve),
+            ["manual", percent] => match percent.trim_end_matches('%').parse::<u8>() {
+                Ok(percent) if percent <= 100 => Ok(FanMode::Manual(percent)),
+                _ => Err(format!("Invalid fan duty (expected 0-100): {}", percent)),
+            },
             _ => Err(format!("Invalid fan mode: {}", s)),
         }
     }
 }
 
+impl_serde_via_str!(FanMode);
+
 /// Hardware-level fan modes (what actually gets written to device)
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum HardwareFanMode {
-    Max,  // Writing 0 to device
-    Bios, // Writing 2 to device
+    Max,        // Writing 0 to device
+    Bios,       // Writing 2 to device
+    Manual(u8), // Writing 1 to device plus a pwm duty (percent)
 }
 
 /// Status information from the daemon
@@ -61,19 +93,56 @@ impl fmt::Display for FanStatus {
 }
 
 /// Performance modes that write to /sys/firmware/acpi/platform_profile
+/// The hardware advertises which of these it supports in platform_profile_choices
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum PerformanceMode {
+    LowPower,
+    Cool,
+    Quiet,
     PowerSaver,
     Balanced,
+    BalancedPerformance,
     Performance,
+    MaxPower,
+    Custom,
+}
+
+impl PerformanceMode {
+    /// Modes offered when the hardware choices are unknown
+    pub const DEFAULTS: [PerformanceMode; 3] = [
+        PerformanceMode::PowerSaver,
+        PerformanceMode::Balanced,
+        PerformanceMode::Performance,
+    ];
+
+    /// Human readable name for menus
+    pub fn label(&self) -> &'static str {
+        match self {
+            PerformanceMode::LowPower => "Low Power",
+            PerformanceMode::Cool => "Cool",
+            PerformanceMode::Quiet => "Quiet",
+            PerformanceMode::PowerSaver => "Power Saver",
+            PerformanceMode::Balanced => "Balanced",
+            PerformanceMode::BalancedPerformance => "Balanced Performance",
+            PerformanceMode::Performance => "Performance",
+            PerformanceMode::MaxPower => "Max Power",
+            PerformanceMode::Custom => "Custom",
+        }
+    }
 }
 
 impl fmt::Display for PerformanceMode {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
+            PerformanceMode::LowPower => write!(f, "low-power"),
+            PerformanceMode::Cool => write!(f, "cool"),
+            PerformanceMode::Quiet => write!(f, "quiet"),
             PerformanceMode::PowerSaver => write!(f, "power-saver"),
             PerformanceMode::Balanced => write!(f, "balanced"),
+            PerformanceMode::BalancedPerformance => write!(f, "balanced-performance"),
             PerformanceMode::Performance => write!(f, "performance"),
+            PerformanceMode::MaxPower => write!(f, "max-power"),
+            PerformanceMode::Custom => write!(f, "custom"),
         }
     }
 }
@@ -83,27 +152,200 @@ impl std::str::FromStr for PerformanceMode {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s.to_lowercase().as_str() {
+            "low-power" => Ok(PerformanceMode::LowPower),
+            "cool" => Ok(PerformanceMode::Cool),
+            "quiet" => Ok(PerformanceMode::Quiet),
             "power-saver" => Ok(PerformanceMode::PowerSaver),
             "balanced" => Ok(PerformanceMode::Balanced),
+            "balanced-performance" => Ok(PerformanceMode::BalancedPerformance),
             "performance" => Ok(PerformanceMode::Performance),
+            "max-power" => Ok(PerformanceMode::MaxPower),
+            "custom" => Ok(PerformanceMode::Custom),
             _ => Err(format!("Invalid performance mode: {}", s)),
         }
     }
 }
 
+impl_serde_via_str!(PerformanceMode);
+
+/// Keyboard backlight level from /sys/class/leds/*kbd_backlight*
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyboardBacklight {
+    pub brightness: u32,
+    pub max_brightness: u32,
+}
+
+impl fmt::Display for KeyboardBacklight {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.brightness, self.max_brightness)
+    }
+}
+
+impl std::str::FromStr for KeyboardBacklight {
+    type Err = String;
+
+    /// Parses `brightness/max_brightness`, e.g. `2/3`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid keyboard backlight: {}", s);
+        let (brightness, max_brightness) = s.split_once('/').ok_or_else(invalid)?;
+        Ok(KeyboardBacklight {
+            brightness: brightness.trim().parse().map_err(|_| invalid())?,
+            max_brightness: max_brightness.trim().parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// Battery charge thresholds in percent, charging stops at `end` and
+/// resumes below `start` (if the battery supports a start threshold)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChargeLimit {
+    pub start: Option<u8>,
+    pub end: u8,
+}
+
+impl fmt::Display for ChargeLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.start {
+            Some(start) => write!(f, "{}-{}%", start, self.end),
+            None => write!(f, "{}%", self.end),
+        }
+    }
+}
+
+impl std::str::FromStr for ChargeLimit {
+    type Err = String;
+
+    /// Parses `end` or `start-end`, e.g. `80` or `75-80%`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "Invalid charge limit (expected end or start-end, end 1-100): {}",
+                s
+            )
+        };
+        // Many drivers report a start threshold of 0, meaning charge whenever below the end
+        let parse = |value: &str, min: u8| {
+            value
+                .trim()
+                .trim_end_matches('%')
+                .parse::<u8>()
+                .ok()
+                .filter(|value| (min..=100).contains(value))
+                .ok_or_else(invalid)
+        };
+        let limit = match s.split_once('-') {
+            Some((start, end)) => ChargeLimit {
+                start: Some(parse(start, 0)?),
+                end: parse(end, 1)?,
+            },
+            None => ChargeLimit {
+                start: None,
+                end: parse(s, 1)?,
+            },
+        };
+        match limit.start {
+            Some(start) if start >= limit.end => Err(invalid()),
+            _ => Ok(limit),
+        }
+    }
+}
+
 /// Current system state
 #[derive(Debug, Clone)]
 pub struct SystemState {
     pub fan_mode: FanMode,
-    pub performance_mode: PerformanceMode,
+    pub performance_mode: Option<PerformanceMode>, // None if unknown
+    pub performance_choices: Vec<PerformanceMode>, // empty if unknown
     pub temperature: Option<i32>,
+    pub fan_speeds: Vec<u32>, // in RPM, one per fan
+    pub keyboard_backlight: Option<KeyboardBacklight>,
+    pub battery_capacity: Option<u8>, // in percent
+    pub charge_limit: Option<ChargeLimit>,
     pub error_message: Option<String>,
+    pub alert: Option<String>, // critical temperature alert from the daemon
 }
 
 /// Messages sent from GUI to daemon
 pub enum TrayMessage {
     SetMode(FanMode),
     SetPerformanceMode(PerformanceMode),
+    SetKeyboardBacklight(u32),
+    SetChargeLimit(ChargeLimit),
     GetState,
     Exit,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fan_mode_parses_socket_names() {
+        assert_eq!("max".parse::<FanMode>(), Ok(FanMode::Max));
+        assert_eq!("Auto".parse::<FanMode>(), Ok(FanMode::Auto));
+        assert_eq!("bios".parse::<FanMode>(), Ok(FanMode::Bios));
+        assert_eq!("curve".parse::<FanMode>(), Ok(FanMode::Curve));
+        assert_eq!("manual 40".parse::<FanMode>(), Ok(FanMode::Manual(40)));
+        assert_eq!("Manual 40%".parse::<FanMode>(), Ok(FanMode::Manual(40)));
+    }
+
+    #[test]
+    fn fan_mode_rejects_invalid_values() {
+        assert!("turbo".parse::<FanMode>().is_err());
+        assert!("manual".parse::<FanMode>().is_err());
+        assert!("manual 101".parse::<FanMode>().is_err());
+        assert!("manual -5".parse::<FanMode>().is_err());
+    }
+
+    #[test]
+    fn charge_limit_parses_end_and_range() {
+        assert_eq!(
+            "80".parse::<ChargeLimit>(),
+            Ok(ChargeLimit {
+                start: None,
+                end: 80
+            })
+        );
+        assert_eq!(
+            "75-80%".parse::<ChargeLimit>(),
+            Ok(ChargeLimit {
+                start: Some(75),
+                end: 80
+            })
+        );
+        assert_eq!(
+            "75-80".parse::<ChargeLimit>().unwrap().to_string(),
+            "75-80%"
+        );
+        assert_eq!(
+            "0-100%".parse::<ChargeLimit>(),
+            Ok(ChargeLimit {
+                start: Some(0),
+                end: 100
+            })
+        );
+    }
+
+    #[test]
+    fn charge_limit_rejects_invalid_ranges() {
+        assert!("0".parse::<ChargeLimit>().is_err());
+        assert!("0-0".parse::<ChargeLimit>().is_err());
+        assert!("101".parse::<ChargeLimit>().is_err());
+        assert!("80-75".parse::<ChargeLimit>().is_err());
+        assert!("80-80".parse::<ChargeLimit>().is_err());
+        assert!("full".parse::<ChargeLimit>().is_err());
+    }
+
+    #[test]
+    fn fan_mode_round_trips_through_display() {
+        for mode in [
+            FanMode::Max,
+            FanMode::Auto,
+            FanMode::Bios,
+            FanMode::Manual(55),
+            FanMode::Curve,
+        ] {
+            assert_eq!(mode.to_string().parse::<FanMode>(), Ok(mode));
+        }
+    }
+}
@@ -66,6 +66,26 @@ fn main() {
                         let _ = tx_refresh_clone.send(());
                     }
                 }
+                TrayMessage::SetKeyboardBacklight(brightness) => {
+                    info!("Setting keyboard backlight to: {}...", brightness);
+                    if let Err(e) = daemon_client.set_keyboard_backlight(brightness) {
+                        error!("Failed to set keyboard backlight: {}", e);
+                    } else {
+                        info!("✓ Keyboard backlight set to: {}", brightness);
+                        // Signal that we should refresh the tray menu
+                        let _ = tx_refresh_clone.send(());
+                    }
+                }
+                TrayMessage::SetChargeLimit(limit) => {
+                    info!("Setting charge limit to: {}...", limit);
+                    if let Err(e) = daemon_client.set_charge_limit(limit) {
+                        error!("Failed to set charge limit: {}", e);
+                    } else {
+                        info!("✓ Charge limit set to: {}", limit);
+                        // Signal that we should refresh the tray menu
+                        let _ = tx_refresh_clone.send(());
+                    }
+                }
                 TrayMessage::GetState => {
                     // This message is used to trigger menu updates
                     debug!("State refresh requested");
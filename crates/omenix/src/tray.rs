@@ -2,22 +2,38 @@ use std::sync::{mpsc, Arc, Mutex};
 use tracing::{debug, info, warn};
 use tray_icon::{
     TrayIconBuilder, TrayIconEvent,
-    menu::{Menu, MenuEvent, MenuId, MenuItem, PredefinedMenuItem, Submenu},
+    menu::{IsMenuItem, Menu, MenuEvent, MenuId, MenuItem, PredefinedMenuItem, Submenu},
 };
 
 use omenix_lib::client::DaemonClient;
-use omenix_lib::types::{FanMode, PerformanceMode, SystemState, TrayMessage};
+use omenix_lib::types::{ChargeLimit, FanMode, PerformanceMode, SystemState, TrayMessage};
 
 // Define menu IDs as constants
 const FAN_MAX_ID: &str = "fan_max";
 const FAN_AUTO_ID: &str = "fan_auto";
 const FAN_BIOS_ID: &str = "fan_bios";
+const FAN_CURVE_ID: &str = "fan_curve";
+const FAN_MANUAL_ID_PREFIX: &str = "fan_manual_";
 
-const PERF_POWER_SAVER_ID: &str = "perf_power_saver";
-const PERF_BALANCED_ID: &str = "perf_balanced";
-const PERF_PERFORMANCE_ID: &str = "perf_performance";
+// Duty steps offered in the manual fan submenu (percent)
+const MANUAL_DUTY_STEPS: [u8; 5] = [20, 40, 60, 80, 100];
 
+const PERF_ID_PREFIX: &str = "perf_";
+
+const KBD_ID_PREFIX: &str = "kbd_";
+
+// Backlights with up to this many levels list each one, brighter LED classes
+// (e.g. max_brightness 255) get percentage steps of the maximum instead
+const KBD_MAX_LISTED_LEVELS: u32 = 5;
+const KBD_BRIGHTNESS_STEPS: [u32; 4] = [25, 50, 75, 100];
+
+// Charge limits offered in the battery submenu (percent)
+const CHARGE_LIMIT_ID_PREFIX: &str = "charge_";
+const CHARGE_LIMIT_STEPS: [u8; 4] = [60, 80, 90, 100];
+
+const SENSORS_ID: &str = "sensors";
 const ERROR_MESSAGE_ID: &str = "error";
+const ALERT_ID: &str = "alert";
 
 const QUIT_ID: &str = "quit";
 
@@ -83,6 +99,7 @@ impl TrayManager {
         let fan_max_id = MenuId::new(FAN_MAX_ID);
         let fan_auto_id = MenuId::new(FAN_AUTO_ID);
         let fan_bios_id = MenuId::new(FAN_BIOS_ID);
+        let fan_curve_id = MenuId::new(FAN_CURVE_ID);
 
         let fan_max_label = if fan_current_mode == FanMode::Max {
             "• Max Performance"
@@ -99,51 +116,181 @@ impl TrayManager {
         } else {
             "BIOS Default"
         };
+        let fan_curve_label = if fan_current_mode == FanMode::Curve {
+            "• Fan Curve"
+        } else {
+            "Fan Curve"
+        };
 
         let fan_max = MenuItem::with_id(fan_max_id, fan_max_label, true, None);
         let fan_auto = MenuItem::with_id(fan_auto_id, fan_auto_label, true, None);
         let fan_bios = MenuItem::with_id(fan_bios_id, fan_bios_label, true, None);
+        let fan_curve = MenuItem::with_id(fan_curve_id, fan_curve_label, true, None);
+
+        let fan_manual_items: Vec<MenuItem> = MANUAL_DUTY_STEPS
+            .iter()
+            .map(|&percent| {
+                let id = MenuId::new(format!("{}{}", FAN_MANUAL_ID_PREFIX, percent));
+                let label = if fan_current_mode == FanMode::Manual(percent) {
+                    format!("• {}%", percent)
+                } else {
+                    format!("{}%", percent)
+                };
+                MenuItem::with_id(id, label, true, None)
+            })
+            .collect();
+        let fan_manual_refs: Vec<&dyn IsMenuItem> = fan_manual_items
+            .iter()
+            .map(|item| item as &dyn IsMenuItem)
+            .collect();
+        let fan_manual_label = if matches!(fan_current_mode, FanMode::Manual(_)) {
+            "• Manual Duty"
+        } else {
+            "Manual Duty"
+        };
+        let fan_manual = Submenu::with_items(fan_manual_label, true, &fan_manual_refs)
+            .expect("Failed to create manual fan submenu");
 
-        let fan_submenu =
-            Submenu::with_items(&fan_menu_label, true, &[&fan_max, &fan_auto, &fan_bios])
-                .expect("Failed to create fan submenu");
+        let fan_submenu = Submenu::with_items(
+            &fan_menu_label,
+            true,
+            &[&fan_max, &fan_auto, &fan_curve, &fan_bios, &fan_manual],
+        )
+        .expect("Failed to create fan submenu");
 
         // Performance mode submenu
         let perf_current_mode = state.performance_mode;
-        let perf_menu_label = format!("⚡ Performance: {}", perf_current_mode);
-
-        let perf_power_saver_id = MenuId::new(PERF_POWER_SAVER_ID);
-        let perf_balanced_id = MenuId::new(PERF_BALANCED_ID);
-        let perf_performance_id = MenuId::new(PERF_PERFORMANCE_ID);
-
-        let perf_power_saver_label = if perf_current_mode == PerformanceMode::PowerSaver {
-            "• Power Saver"
-        } else {
-            "Power Saver"
+        let perf_menu_label = match perf_current_mode {
+            Some(mode) => format!("⚡ Performance: {}", mode),
+            None => "⚡ Performance: Unknown".to_string(),
         };
-        let perf_balanced_label = if perf_current_mode == PerformanceMode::Balanced {
-            "• Balanced"
+
+        // Only offer the profiles the hardware supports
+        let perf_choices: &[PerformanceMode] = if state.performance_choices.is_empty() {
+            &PerformanceMode::DEFAULTS
         } else {
-            "Balanced"
+            &state.performance_choices
         };
-        let perf_performance_label = if perf_current_mode == PerformanceMode::Performance {
-            "• Performance"
-        } else {
-            "Performance"
+        let perf_items: Vec<MenuItem> = perf_choices
+            .iter()
+            .map(|&mode| {
+                let id = MenuId::new(format!("{}{}", PERF_ID_PREFIX, mode));
+                let label = if perf_current_mode == Some(mode) {
+                    format!("• {}", mode.label())
+                } else {
+                    mode.label().to_string()
+                };
+                MenuItem::with_id(id, label, true, None)
+            })
+            .collect();
+        let perf_refs: Vec<&dyn IsMenuItem> = perf_items
+            .iter()
+            .map(|item| item as &dyn IsMenuItem)
+            .collect();
+
+        let perf_submenu = Submenu::with_items(&perf_menu_label, true, &perf_refs)
+            .expect("Failed to create performance submenu");
+
+        // Keyboard backlight submenu, only shown when the machine has one
+        let kbd_items: Vec<MenuItem> = match state.keyboard_backlight {
+            Some(backlight) => {
+                let levels: Vec<(u32, String)> =
+                    if backlight.max_brightness <= KBD_MAX_LISTED_LEVELS {
+                        (1..=backlight.max_brightness)
+                            .map(|level| (level, format!("Level {}", level)))
+                            .collect()
+                    } else {
+                        KBD_BRIGHTNESS_STEPS
+                            .iter()
+                            .map(|&percent| {
+                                let level = (backlight.max_brightness * percent + 50) / 100;
+                                (level.max(1), format!("{}%", percent))
+                            })
+                            .collect()
+                    };
+                std::iter::once((0, "Off".to_string()))
+                    .chain(levels)
+                    .map(|(level, name)| {
+                        let id = MenuId::new(format!("{}{}", KBD_ID_PREFIX, level));
+                        let label = if backlight.brightness == level {
+                            format!("• {}", name)
+                        } else {
+                            name
+                        };
+                        MenuItem::with_id(id, label, true, None)
+                    })
+                    .collect()
+            }
+            None => Vec::new(),
         };
+        let kbd_refs: Vec<&dyn IsMenuItem> = kbd_items
+            .iter()
+            .map(|item| item as &dyn IsMenuItem)
+            .collect();
+        let kbd_submenu = state.keyboard_backlight.map(|backlight| {
+            Submenu::with_items(format!("⌨️ Keyboard: {}", backlight), true, &kbd_refs)
+                .expect("Failed to create keyboard backlight submenu")
+        });
 
-        let perf_power_saver =
-            MenuItem::with_id(perf_power_saver_id, perf_power_saver_label, true, None);
-        let perf_balanced = MenuItem::with_id(perf_balanced_id, perf_balanced_label, true, None);
-        let perf_performance =
-            MenuItem::with_id(perf_performance_id, perf_performance_label, true, None);
+        // Battery charge limit submenu, only shown when the battery supports one
+        let charge_items: Vec<MenuItem> = match state.charge_limit {
+            Some(limit) => CHARGE_LIMIT_STEPS
+                .iter()
+                .map(|&end| {
+                    // The start threshold (if any) is passed along so the click handler can keep the gap
+                    let id = match limit.start {
+                        Some(start) => MenuId::new(format!(
+                            "{}{}-{}",
+                            CHARGE_LIMIT_ID_PREFIX,
+                            end.saturating_sub(limit.end - start).max(1),
+                            end
+                        )),
+                        None => MenuId::new(format!("{}{}", CHARGE_LIMIT_ID_PREFIX, end)),
+                    };
+                    let label = if limit.end == end {
+                        format!("• Limit {}%", end)
+                    } else {
+                        format!("Limit {}%", end)
+                    };
+                    MenuItem::with_id(id, label, true, None)
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+        let charge_refs: Vec<&dyn IsMenuItem> = charge_items
+            .iter()
+            .map(|item| item as &dyn IsMenuItem)
+            .collect();
+        let charge_submenu = state.charge_limit.map(|limit| {
+            let capacity = match state.battery_capacity {
+                Some(capacity) => format!("{}%", capacity),
+                None => "Unknown".to_string(),
+            };
+            Submenu::with_items(
+                format!("🔋 Battery: {} (limit {})", capacity, limit),
+                true,
+                &charge_refs,
+            )
+            .expect("Failed to create battery submenu")
+        });
 
-        let perf_submenu = Submenu::with_items(
-            &perf_menu_label,
-            true,
-            &[&perf_power_saver, &perf_balanced, &perf_performance],
-        )
-        .expect("Failed to create performance submenu");
+        // Sensor readings item (disabled)
+        let temp_label = match state.temperature {
+            Some(temp) => format!("🌡️ {}°C", temp / 1000),
+            None => "🌡️ Unknown".to_string(),
+        };
+        let fan_labels: Vec<String> = state
+            .fan_speeds
+            .iter()
+            .enumerate()
+            .map(|(i, rpm)| format!("Fan {}: {} RPM", i + 1, rpm))
+            .collect();
+        let sensors_label = if fan_labels.is_empty() {
+            temp_label
+        } else {
+            format!("{} | {}", temp_label, fan_labels.join(" | "))
+        };
+        let sensors = MenuItem::with_id(MenuId::new(SENSORS_ID), sensors_label, false, None);
 
         // error message item (disabled)
         let error_message_id = MenuId::new(ERROR_MESSAGE_ID);
@@ -157,20 +304,36 @@ impl TrayManager {
             None,
         );
 
+        // critical temperature alert item (disabled), only while the daemon reports one
+        let alert = state.alert.as_ref().map(|alert| {
+            MenuItem::with_id(MenuId::new(ALERT_ID), format!("⚠ {}", alert), false, None)
+        });
+
         // Quit item
         let quit_id = MenuId::new(QUIT_ID);
         let quit = MenuItem::with_id(quit_id, "Quit", true, None);
         let separator = PredefinedMenuItem::separator();
 
-        Menu::with_items(&[
-            &fan_submenu,
-            &perf_submenu,
-            &separator,
+        let mut items: Vec<&dyn IsMenuItem> = Vec::new();
+        if let Some(alert) = &alert {
+            items.push(alert);
+        }
+        items.extend([&fan_submenu as &dyn IsMenuItem, &perf_submenu]);
+        if let Some(kbd_submenu) = &kbd_submenu {
+            items.push(kbd_submenu);
+        }
+        if let Some(charge_submenu) = &charge_submenu {
+            items.push(charge_submenu);
+        }
+        items.extend([
+            &separator as &dyn IsMenuItem,
+            &sensors,
             &separator,
             &error_message,
             &quit,
-        ])
-        .expect("Failed to create menu")
+        ]);
+
+        Menu::with_items(&items).expect("Failed to create menu")
     }
 
     fn create_menu(client: &DaemonClient) -> Menu {
@@ -181,9 +344,15 @@ impl TrayManager {
                 // Fallback for when we can't get state
                 Self::create_menu_with_state(&SystemState {
                     fan_mode: FanMode::Auto,
-                    performance_mode: PerformanceMode::Balanced,
+                    performance_mode: None,
+                    performance_choices: Vec::new(),
                     temperature: None,
+                    fan_speeds: Vec::new(),
+                    keyboard_backlight: None,
+                    battery_capacity: None,
+                    charge_limit: None,
                     error_message: Some(format!("Unable to connect to daemon: {}", e)),
+                    alert: None,
                 })
             }
         }
@@ -199,7 +368,14 @@ impl TrayManager {
                         // Compare states to see if update is needed
                         old_state.fan_mode != current_state.fan_mode
                             || old_state.performance_mode != current_state.performance_mode
+                            || old_state.performance_choices != current_state.performance_choices
                             || old_state.temperature != current_state.temperature
+                            || old_state.fan_speeds != current_state.fan_speeds
+                            || old_state.keyboard_backlight != current_state.keyboard_backlight
+                            || old_state.battery_capacity != current_state.battery_capacity
+                            || old_state.charge_limit != current_state.charge_limit
+                            || old_state.error_message != current_state.error_message
+                            || old_state.alert != current_state.alert
                     }
                     None => true, // First time, always update
                 };
@@ -218,9 +394,15 @@ impl TrayManager {
                 debug!("Error getting current state: {}", e);
                 let error_state = SystemState {
                     fan_mode: FanMode::Auto,
-                    performance_mode: PerformanceMode::Balanced,
+                    performance_mode: None,
+                    performance_choices: Vec::new(),
                     temperature: None,
+                    fan_speeds: Vec::new(),
+                    keyboard_backlight: None,
+                    battery_capacity: None,
+                    charge_limit: None,
                     error_message: Some(e.to_string()),
+                    alert: None,
                 };
                 let new_menu = Self::create_menu_with_state(&error_state);
                 self.tray_icon.set_menu(Some(Box::new(new_menu)));
@@ -257,20 +439,45 @@ impl TrayManager {
                         info!("BIOS Default menu item clicked");
                         let _ = tx.send(TrayMessage::SetMode(FanMode::Bios));
                     }
-                    PERF_POWER_SAVER_ID => {
-                        info!("Power Saver performance mode clicked");
-                        let _ =
-                            tx.send(TrayMessage::SetPerformanceMode(PerformanceMode::PowerSaver));
+                    FAN_CURVE_ID => {
+                        info!("Fan Curve menu item clicked");
+                        let _ = tx.send(TrayMessage::SetMode(FanMode::Curve));
+                    }
+                    id if id.starts_with(FAN_MANUAL_ID_PREFIX) => {
+                        match id[FAN_MANUAL_ID_PREFIX.len()..].parse::<u8>() {
+                            Ok(percent) => {
+                                info!("Manual {}% menu item clicked", percent);
+                                let _ = tx.send(TrayMessage::SetMode(FanMode::Manual(percent)));
+                            }
+                            Err(_) => warn!("Invalid manual fan menu ID: {}", id),
+                        }
+                    }
+                    id if id.starts_with(PERF_ID_PREFIX) => {
+                        match id[PERF_ID_PREFIX.len()..].parse::<PerformanceMode>() {
+                            Ok(mode) => {
+                                info!("{} performance mode clicked", mode.label());
+                                let _ = tx.send(TrayMessage::SetPerformanceMode(mode));
+                            }
+                            Err(_) => warn!("Invalid performance menu ID: {}", id),
+                        }
                     }
-                    PERF_BALANCED_ID => {
-                        info!("Balanced performance mode clicked");
-                        let _ = tx.send(TrayMessage::SetPerformanceMode(PerformanceMode::Balanced));
+                    id if id.starts_with(KBD_ID_PREFIX) => {
+                        match id[KBD_ID_PREFIX.len()..].parse::<u32>() {
+                            Ok(brightness) => {
+                                info!("Keyboard backlight {} menu item clicked", brightness);
+                                let _ = tx.send(TrayMessage::SetKeyboardBacklight(brightness));
+                            }
+                            Err(_) => warn!("Invalid keyboard backlight menu ID: {}", id),
+                        }
                     }
-                    PERF_PERFORMANCE_ID => {
-                        info!("Performance mode clicked");
-                        let _ = tx.send(TrayMessage::SetPerformanceMode(
-                            PerformanceMode::Performance,
-                        ));
+                    id if id.starts_with(CHARGE_LIMIT_ID_PREFIX) => {
+                        match id[CHARGE_LIMIT_ID_PREFIX.len()..].parse::<ChargeLimit>() {
+                            Ok(limit) => {
+                                info!("Charge limit {} menu item clicked", limit);
+                                let _ = tx.send(TrayMessage::SetChargeLimit(limit));
+                            }
+                            Err(_) => warn!("Invalid charge limit menu ID: {}", id),
+                        }
                     }
                     QUIT_ID => {
                         info!("Quit menu item clicked");
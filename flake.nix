@@ -100,6 +100,8 @@
         installPhase = ''
           mkdir -p $out/bin
           ln -s ${workspace}/bin/omenix-daemon $out/bin/omenix-daemon
+          install -Dm644 ${src}/crates/omenix-daemon/net.hadess.PowerProfiles.conf \
+            $out/share/dbus-1/system.d/omenix-daemon.conf
         '';
 
         meta = meta // {
@@ -171,6 +173,7 @@
               };
 
               environment.systemPackages = [ config.services.omenix-daemon.package ];
+              services.dbus.packages = [ config.services.omenix-daemon.package ];
             })
             (mkIf config.programs.omenix.enable {
               environment.systemPackages = [ config.programs.omenix.package ];